@@ -2,12 +2,63 @@ mod render_pipeline;
 mod mesh;
 mod ortho_camera;
 mod vector_graphics;
+mod software_rasterizer;
 use vector_graphics::VectorGraphics;
 
 use crate::render_pipeline::*;
 
+// Plays the saved level, or a recording made with --record, for a number of frames without a window or wgpu,
+// writes the last frame to a PPM file and prints the state hash. A recording brings its own physics, so --physics
+// only applies to the saved level.
+// Usage: vector_graphics --headless <frames> <output.ppm> [--replay <recording>] [--physics <config>]
+fn run_headless(args:&[String]){
+    let frames:usize = args.first().and_then(|a| a.parse().ok()).unwrap_or(1);
+    let path = args.get(1).map(|a| a.as_str()).unwrap_or("frame.ppm");
+    let (_x,_y,width,height) = render_pipeline::get_window_rect();
+    let mut vector_graphics = VectorGraphics::new();
+    vector_graphics.resize(width as f32, height as f32);
+    if let Some(physics) = flag_value(args, "--physics") {
+        vector_graphics.load_physics(physics).unwrap();
+    }
+    match flag_value(args, "--replay") {
+        Some(replay) => vector_graphics.load_replay(replay).unwrap(),
+        None => vector_graphics.play_level(),
+    }
+    for _ in 0..frames{
+        vector_graphics.update();
+        for message in vector_graphics.take_messages() {
+            println!("{}", message);
+        }
+        for event in vector_graphics.take_trigger_events() {
+            println!("{:?}", event);
+        }
+    }
+    println!("state hash {:016x}", vector_graphics.state_hash());
+    let mut geometry = mesh::Geometry::new(2000);
+    let mut minimap_geometry = mesh::Geometry::new(2000);
+    let mut image = software_rasterizer::Image::new(width as usize, height as usize);
+    vector_graphics.draw(&mut geometry);
+    image.rasterize(&geometry, vector_graphics.view(), (0.0, 0.0, width as f32, height as f32));
+    if let Some(minimap) = vector_graphics.draw_minimap(&mut minimap_geometry) {
+        image.rasterize(&minimap_geometry, minimap.view, minimap.viewport);
+    }
+    image.save_ppm(path).unwrap();
+}
+
+fn flag_value<'a>(args:&'a [String], flag:&str) -> Option<&'a str>{
+    args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).map(|a| a.as_str())
+}
+
+// Usage: vector_graphics [--record <recording>] [--physics <config>], recording plays the saved level and is written
+// when the window closes. The physics config is a JSON file with any of the PhysicsConfig fields.
+#[allow(clippy::single_match)]
 pub fn main() {
     env_logger::init();
+    let args:Vec<String> = std::env::args().collect();
+    if args.get(1).map(|a| a.as_str()) == Some("--headless") {
+        run_headless(&args[2..]);
+        return;
+    }
     let event_loop = winit::event_loop::EventLoop::new().unwrap();
     let (x,y,width,height) = render_pipeline::get_window_rect();
     let window = winit::window::WindowBuilder::new()
@@ -17,6 +68,7 @@ pub fn main() {
         .unwrap();
 
     let size = window.inner_size();
+    let mut scale_factor = window.scale_factor();
     let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
         backends: wgpu::Backends::GL,
         ..Default::default()
@@ -40,8 +92,7 @@ pub fn main() {
     let surface_caps = surface.get_capabilities(&adapter);
     let surface_format = surface_caps.formats.iter()
         .copied()
-        .filter(|f| f.is_srgb())
-        .next()
+        .find(|f| f.is_srgb())
         .unwrap_or(surface_caps.formats[0]);
     let mut config = wgpu::SurfaceConfiguration {
         usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -56,13 +107,25 @@ pub fn main() {
     surface.configure(&device, &config);
     let mut render_pipeline = RenderPipeline::new(&device, &config);
     let mut vector_graphics = VectorGraphics::new();
+    let logical_size = size.to_logical::<f32>(scale_factor);
+    vector_graphics.resize(logical_size.width, logical_size.height);
+    render_pipeline.resize(&queue, logical_size.width, logical_size.height, scale_factor as f32);
+    if let Some(physics) = flag_value(&args, "--physics") {
+        vector_graphics.load_physics(physics).unwrap();
+    }
+    let record = flag_value(&args, "--record");
+    if record.is_some() {
+        vector_graphics.play_level();
+        vector_graphics.start_recording();
+    }
 
     event_loop.run( |event, target|{
         match event {
             winit::event::Event::WindowEvent { window_id:_window_id, event } => {
                 match event {
                     winit::event::WindowEvent::CursorMoved { device_id:_device_id, position } => {
-                        vector_graphics.mousemove(vector_graphics::Vector2 { x: position.x as f32, y: position.y as f32 });
+                        let position = position.to_logical::<f32>(scale_factor);
+                        vector_graphics.mousemove(vector_graphics::Vector2 { x: position.x, y: position.y });
                     }
                     winit::event::WindowEvent::KeyboardInput { device_id:_device_id, event, is_synthetic:_is_synthetic } =>{
                         match event.physical_key{
@@ -76,7 +139,19 @@ pub fn main() {
                         }
                     }
                     winit::event::WindowEvent::RedrawRequested => {
-                        vector_graphics.update(&mut render_pipeline.mesh, &queue);
+                        vector_graphics.update();
+                        // There is no text rendering, so the latest message goes in the title bar.
+                        if let Some(message) = vector_graphics.take_messages().pop() {
+                            window.set_title(&message);
+                        }
+                        for event in vector_graphics.take_trigger_events() {
+                            log::debug!("{:?}", event);
+                        }
+                        vector_graphics.draw(&mut render_pipeline.mesh.geometry);
+                        let minimap = vector_graphics.draw_minimap(&mut render_pipeline.minimap_mesh.geometry);
+                        render_pipeline.set_view(&queue, vector_graphics.view());
+                        render_pipeline.set_minimap(&queue, minimap);
+                        render_pipeline.update_queue(&queue);
                         render_pipeline.render(&surface, &device, &queue);
                         window.request_redraw();
                     }
@@ -84,10 +159,31 @@ pub fn main() {
                         config.width = new_size.width;
                         config.height = new_size.height;
                         surface.configure(&device, &config);
-                        vector_graphics.resize(new_size.width as f32, new_size.height as f32);
-                        render_pipeline.resize(&queue, new_size.width as f32, new_size.height as f32);
+                        let logical_size = new_size.to_logical::<f32>(scale_factor);
+                        vector_graphics.resize(logical_size.width, logical_size.height);
+                        render_pipeline.resize(&queue, logical_size.width, logical_size.height, scale_factor as f32);
+                    }
+                    winit::event::WindowEvent::ScaleFactorChanged { scale_factor:new_scale_factor, mut inner_size_writer } => {
+                        // Keeps the logical size, the surface still has the physical size of the old scale factor.
+                        let requested = winit::dpi::PhysicalSize::new(config.width, config.height)
+                            .to_logical::<f64>(scale_factor)
+                            .to_physical::<u32>(new_scale_factor);
+                        let new_size = match inner_size_writer.request_inner_size(requested) {
+                            Ok(()) => requested,
+                            Err(_) => window.inner_size(),
+                        };
+                        scale_factor = new_scale_factor;
+                        let logical_size = new_size.to_logical::<f32>(scale_factor);
+                        config.width = new_size.width;
+                        config.height = new_size.height;
+                        surface.configure(&device, &config);
+                        vector_graphics.resize(logical_size.width, logical_size.height);
+                        render_pipeline.resize(&queue, logical_size.width, logical_size.height, scale_factor as f32);
                     }
                     winit::event::WindowEvent::CloseRequested => {
+                        if let Some(record) = record {
+                            vector_graphics.save_recording(record).unwrap();
+                        }
                         target.exit();
                     }
                     _=>{}
@@ -2,9 +2,28 @@ use wgpu::util::DeviceExt;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 2],
-    color: [f32; 3],
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub color: [f32; 3],
+}
+
+// One instance of the signed distance field quad. params holds corner radius, border width and blur,
+// a border width of zero fills the shape and a blur above zero turns it into a soft shadow.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SdfQuad {
+    pub rect: [f32; 4],
+    pub color: [f32; 4],
+    pub params: [f32; 4],
+}
+
+// A run of triangles followed by a run of sdf quads, given as the index and sdf quad counts each run ends at.
+// Drawing the layers in order draws everything in the order it was added, so an sdf quad covers the
+// triangles added before it and is covered by the ones added after it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub indices: usize,
+    pub sdf_quads: usize,
 }
 
 struct FixedSizeVec<T> where T:Copy{
@@ -25,25 +44,210 @@ impl<T> FixedSizeVec<T> where T:Copy{
     pub fn clear(&mut self){
         self.length = 0;
     }
+
+    pub fn remaining(&self) -> usize{
+        self.data.len() - self.length
+    }
+
+    pub fn as_slice(&self) -> &[T]{
+        &self.data[..self.length]
+    }
 }
 
-pub struct Mesh{
+// The CPU side of a mesh. It doesn't touch wgpu so it can also be fed to the software rasterizer.
+// The first static_vertices/static_indices entries are a cached static batch that survives clear(),
+// everything after it is the dynamic batch rebuilt each frame. The static batch can only fill part of
+// the buffers, so a dense level loses terrain before it loses the player.
+pub struct Geometry{
     vertices:FixedSizeVec<Vertex>,
     indices:FixedSizeVec<u16>,
+    sdf_quads:FixedSizeVec<SdfQuad>,
+    // Every layer but the last one, which ends with everything added so far.
+    layers:Vec<Layer>,
+    static_vertices:usize,
+    static_indices:usize,
+    static_version:Option<usize>,
+    static_bounds:(f32,f32,f32,f32),
+    static_dirty:bool,
+    building_static:bool,
+}
+
+impl Geometry{
+    pub fn new(capacity:usize) -> Self{
+        let vertices:FixedSizeVec<Vertex> = FixedSizeVec::new(Vertex { position: [0.0,0.0], color: [0.0,0.0,0.0] }, capacity);
+        let indices:FixedSizeVec<u16> = FixedSizeVec::new(0, capacity);
+        let sdf_quads:FixedSizeVec<SdfQuad> = FixedSizeVec::new(SdfQuad { rect: [0.0; 4], color: [0.0; 4], params: [0.0; 4] }, capacity/4);
+        Geometry{vertices, indices, sdf_quads, layers:Vec::new(), static_vertices:0, static_indices:0, static_version:None, static_bounds:(0.0,0.0,0.0,0.0), static_dirty:false, building_static:false}
+    }
+
+    pub fn static_version(&self) -> Option<usize>{
+        self.static_version
+    }
+
+    // The area the static batch was built for, anything outside it may have been culled.
+    pub fn static_bounds(&self) -> (f32,f32,f32,f32){
+        self.static_bounds
+    }
+
+    // Throws away both batches, everything added until end_static becomes the new static batch.
+    pub fn begin_static(&mut self){
+        self.static_vertices = 0;
+        self.static_indices = 0;
+        self.vertices.clear();
+        self.indices.clear();
+        self.sdf_quads.clear();
+        self.layers.clear();
+        self.building_static = true;
+    }
+
+    pub fn end_static(&mut self, version:usize, bounds:(f32,f32,f32,f32)){
+        self.static_vertices = self.vertices.length;
+        self.static_indices = self.indices.length;
+        self.static_version = Some(version);
+        self.static_bounds = bounds;
+        self.static_dirty = true;
+        self.building_static = false;
+    }
+
+    pub fn vertices(&self) -> &[Vertex]{
+        self.vertices.as_slice()
+    }
+
+    pub fn indices(&self) -> &[u16]{
+        self.indices.as_slice()
+    }
+
+    pub fn sdf_quads(&self) -> &[SdfQuad]{
+        self.sdf_quads.as_slice()
+    }
+
+    pub fn layers(&self) -> impl Iterator<Item = Layer> + '_{
+        let last = Layer { indices: self.indices.length, sdf_quads: self.sdf_quads.length };
+        self.layers.iter().copied().chain(std::iter::once(last))
+    }
+
+    // Triangles added after sdf quads have to be drawn after them, so they start a new layer.
+    fn start_triangles(&mut self){
+        let drawn = self.layers.last().map_or(0, |layer| layer.sdf_quads);
+        if self.sdf_quads.length > drawn {
+            self.layers.push(Layer { indices: self.indices.length, sdf_quads: self.sdf_quads.length });
+        }
+    }
+
+    // Whether a shape with this many vertices and indices still fits. Shapes that don't are left out
+    // instead of overflowing the fixed size buffers. While building the static batch a quarter of the
+    // buffers is kept free for the dynamic batch.
+    pub fn has_room(&self, vertices:usize, indices:usize) -> bool{
+        let (reserved_vertices, reserved_indices) = if self.building_static {
+            (self.vertices.data.len() / 4, self.indices.data.len() / 4)
+        } else {
+            (0, 0)
+        };
+        self.vertices.remaining() >= vertices + reserved_vertices && self.indices.remaining() >= indices + reserved_indices
+    }
+
+    // Clears the dynamic batch only. Sdf quads are always part of the dynamic batch, so the static batch
+    // is all in the first layer.
+    pub fn clear(&mut self){
+        self.vertices.length = self.static_vertices;
+        self.indices.length = self.static_indices;
+        self.sdf_quads.clear();
+        self.layers.clear();
+    }
+
+    // Convex polygon drawn as a triangle fan, in either winding order.
+    pub fn add_polygon(&mut self, points:&[[f32; 2]], r:f32, g:f32, b:f32){
+        if points.len() < 3 || !self.has_room(points.len(), (points.len() - 2) * 3) {
+            return;
+        }
+        let mut area = 0.0;
+        for i in 0..points.len(){
+            let a = points[i];
+            let b = points[(i+1) % points.len()];
+            area += a[0]*b[1] - b[0]*a[1];
+        }
+        self.start_triangles();
+        let vertex_id = self.vertices.length as u16;
+        for point in points{
+            self.vertices.push(Vertex { position: *point, color: [r,g,b] });
+        }
+        // Front faces have a negative area with y pointing down, see add_rect.
+        for i in 1..points.len() as u16 - 1{
+            self.indices.push(vertex_id);
+            if area < 0.0 {
+                self.indices.push(vertex_id+i);
+                self.indices.push(vertex_id+i+1);
+            }
+            else{
+                self.indices.push(vertex_id+i+1);
+                self.indices.push(vertex_id+i);
+            }
+        }
+    }
+
+    // A segment drawn as a quad of the given thickness.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_line(&mut self, x1:f32, y1:f32, x2:f32, y2:f32, thickness:f32, r:f32, g:f32, b:f32){
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        let length = (dx*dx + dy*dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let nx = -dy / length * thickness / 2.0;
+        let ny = dx / length * thickness / 2.0;
+        self.add_polygon(&[[x1 + nx, y1 + ny], [x2 + nx, y2 + ny], [x2 - nx, y2 - ny], [x1 - nx, y1 - ny]], r, g, b);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_sdf_quad(&mut self, x:f32, y:f32, width:f32, height:f32, color:[f32; 4], corner_radius:f32, border_width:f32, blur:f32){
+        if self.sdf_quads.remaining() == 0 {
+            return;
+        }
+        let corner_radius = corner_radius.min(width/2.0).min(height/2.0).max(0.0);
+        self.sdf_quads.push(SdfQuad { 
+            rect: [x, y, width, height], 
+            color, 
+            params: [corner_radius, border_width, blur, 0.0],
+        });
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_rect(&mut self, x:f32, y:f32, width:f32, height:f32, r:f32, g:f32, b:f32){
+        if !self.has_room(4, 6) {
+            return;
+        }
+        self.start_triangles();
+        let vertex_id = self.vertices.length as u16;
+        self.vertices.push(Vertex { position: [x,y], color: [r,g,b] });
+        self.vertices.push(Vertex { position: [x+width,y], color: [r,g,b] });
+        self.vertices.push(Vertex { position: [x+width,y+height], color: [r,g,b] });
+        self.vertices.push(Vertex { position: [x,y+height], color: [r,g,b] });
+
+        self.indices.push(vertex_id);
+        self.indices.push(vertex_id+2);
+        self.indices.push(vertex_id+1);
+        self.indices.push(vertex_id);
+        self.indices.push(vertex_id+3);
+        self.indices.push(vertex_id+2);
+    }
+}
+
+pub struct Mesh{
+    pub geometry:Geometry,
     pub vertex_buffer:wgpu::Buffer,
     pub index_buffer:wgpu::Buffer,
-    pub num_indices:u32,
+    pub instance_buffer:wgpu::Buffer,
+    pub layers:Vec<Layer>,
 }
 
 impl Mesh{
     pub fn new(device:&wgpu::Device)->Self{
-        let vertices:FixedSizeVec<Vertex> = FixedSizeVec::new(Vertex { position: [0.0,0.0], color: [0.0,0.0,0.0] }, 2000);
-        let indices:FixedSizeVec<u16> = FixedSizeVec::new(0, 2000);
+        let geometry = Geometry::new(2000);
 
         let vertex_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&vertices.data),
+                contents: bytemuck::cast_slice(&geometry.vertices.data),
                 usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             }
         );
@@ -51,15 +255,23 @@ impl Mesh{
         let index_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(&indices.data),
+                contents: bytemuck::cast_slice(&geometry.indices.data),
                 usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
             }
         );
-        let num_indices = indices.length as u32;
-        Mesh{vertices, indices, vertex_buffer, index_buffer, num_indices}
+
+        let instance_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Sdf Instance Buffer"),
+                contents: bytemuck::cast_slice(&geometry.sdf_quads.data),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+        let layers = geometry.layers().collect();
+        Mesh{geometry, vertex_buffer, index_buffer, instance_buffer, layers}
     }
 
-    pub fn get_vertex_buffer_layout(&self)->wgpu::VertexBufferLayout{
+    pub fn get_vertex_buffer_layout(&self)->wgpu::VertexBufferLayout<'_>{
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex, 
@@ -78,26 +290,59 @@ impl Mesh{
         }
     }
 
-    pub fn add_rect(&mut self, x:f32, y:f32, width:f32, height:f32, r:f32, g:f32, b:f32){
-        let vertex_id = self.vertices.length as u16;
-        self.vertices.push(Vertex { position: [x,y], color: [r,g,b] });
-        self.vertices.push(Vertex { position: [x+width,y], color: [r,g,b] });
-        self.vertices.push(Vertex { position: [x+width,y+height], color: [r,g,b] });
-        self.vertices.push(Vertex { position: [x,y+height], color: [r,g,b] });
-
-        self.indices.push(vertex_id);
-        self.indices.push(vertex_id+2);
-        self.indices.push(vertex_id+1);
-        self.indices.push(vertex_id);
-        self.indices.push(vertex_id+3);
-        self.indices.push(vertex_id+2);
+    pub fn get_instance_buffer_layout(&self)->wgpu::VertexBufferLayout<'_>{
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SdfQuad>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance, 
+            attributes: &[ 
+                wgpu::VertexAttribute {
+                    offset: 0, 
+                    shader_location: 0, 
+                    format: wgpu::VertexFormat::Float32x4, 
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                }
+            ]
+        }
     }
 
+    // Uploads the dynamic batch, and the static batch too if it was rebuilt since the last upload.
     pub fn update_queue(&mut self, queue:&wgpu::Queue){
-        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices.data));
-        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&self.indices.data));
-        self.num_indices = self.indices.length as u32;
-        self.vertices.clear();
-        self.indices.clear();
+        let geometry = &mut self.geometry;
+        let (first_vertex, first_index) = if geometry.static_dirty {
+            (0, 0)
+        } else {
+            (geometry.static_vertices, geometry.static_indices)
+        };
+        // Buffer writes must be aligned to 4 bytes, so the u16 index range is widened to even bounds.
+        let first_index = first_index - first_index % 2;
+        let last_index = (geometry.indices.length + geometry.indices.length % 2).min(geometry.indices.data.len());
+        if first_vertex < geometry.vertices.length {
+            queue.write_buffer(
+                &self.vertex_buffer, 
+                (first_vertex * std::mem::size_of::<Vertex>()) as wgpu::BufferAddress, 
+                bytemuck::cast_slice(&geometry.vertices.data[first_vertex..geometry.vertices.length]));
+        }
+        if first_index < last_index {
+            queue.write_buffer(
+                &self.index_buffer, 
+                (first_index * std::mem::size_of::<u16>()) as wgpu::BufferAddress, 
+                bytemuck::cast_slice(&geometry.indices.data[first_index..last_index]));
+        }
+        if geometry.sdf_quads.length > 0 {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(geometry.sdf_quads.as_slice()));
+        }
+        self.layers.clear();
+        self.layers.extend(geometry.layers());
+        geometry.static_dirty = false;
+        geometry.clear();
     }
-}
\ No newline at end of file
+}
@@ -7,6 +7,11 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.0, 1.0,
 );
 
+// Maps the rect x..x+width, y..y+height to clip space with y pointing down, like the screen.
+pub fn view_matrix(x:f32, y:f32, width:f32, height:f32) -> cgmath::Matrix4<f32>{
+    OPENGL_TO_WGPU_MATRIX * cgmath::ortho(x, x+width, y+height, y, -1.0, 1.0)
+}
+
 pub struct OrthoCamera{
     x:f32,
     y:f32,
@@ -25,15 +30,21 @@ struct CameraUniform {
 
 impl OrthoCamera {
     pub fn set_size(&mut self, queue:&wgpu::Queue, width:f32, height:f32){
+        self.set_rect(queue, self.x, self.y, width, height);
+    }
+
+    pub fn set_rect(&mut self, queue:&wgpu::Queue, x:f32, y:f32, width:f32, height:f32){
+        self.x = x;
+        self.y = y;
         self.width = width;
         self.height = height;
-        let view = OPENGL_TO_WGPU_MATRIX * cgmath::ortho(self.x, self.x+self.width, self.y+self.height, self.y, -1.0, 1.0);
+        let view = view_matrix(self.x, self.y, self.width, self.height);
         let camera_uniform = CameraUniform { view:view.into() };
         queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
     }
 
     pub fn new(device:&wgpu::Device, x:f32, y:f32, width:f32, height:f32) -> Self{
-        let view = (OPENGL_TO_WGPU_MATRIX * cgmath::ortho(x, x+width, y+height, y, -1.0, 1.0)).into();
+        let view = view_matrix(x, y, width, height).into();
         let camera_uniform = CameraUniform { view };
 
         let buffer = device.create_buffer_init(
@@ -1,19 +1,76 @@
 use crate::ortho_camera::*;
 use crate::mesh::*;
+use crate::vector_graphics::MinimapView;
 pub struct RenderPipeline{
     pub mesh:Mesh,
+    pub minimap_mesh:Mesh,
     render_pipeline:wgpu::RenderPipeline,
+    sdf_pipeline:wgpu::RenderPipeline,
     camera:OrthoCamera,
+    minimap_camera:OrthoCamera,
+    minimap_viewport:Option<(f32,f32,f32,f32)>,
+    scale_factor:f32,
+    physical_size:(f32,f32),
 }
 
 pub fn get_window_rect() -> (f64,f64,f64,f64){
     (25.0,25.0,1200.0,800.0)
 }
 
+fn create_pipeline(
+    device:&wgpu::Device, 
+    config:&wgpu::SurfaceConfiguration, 
+    label:&str, 
+    layout:&wgpu::PipelineLayout, 
+    shader:&wgpu::ShaderModule, 
+    buffers:&[wgpu::VertexBufferLayout], 
+    blend:wgpu::BlendState,
+) -> wgpu::RenderPipeline{
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers,
+        },
+        fragment: Some(wgpu::FragmentState { 
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: config.format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1, 
+            mask: !0, 
+            alpha_to_coverage_enabled: false, 
+        },
+        multiview: None,
+    })
+}
+
 impl RenderPipeline{
     pub fn new(device:&wgpu::Device, config:&wgpu::SurfaceConfiguration) -> Self{
         let mesh = Mesh::new(device);
+        let minimap_mesh = Mesh::new(device);
         let camera = OrthoCamera::new(device, 0.0, 0.0, config.width as f32, config.height as f32);
+        let minimap_camera = OrthoCamera::new(device, 0.0, 0.0, config.width as f32, config.height as f32);
 
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
@@ -26,50 +83,79 @@ impl RenderPipeline{
             push_constant_ranges: &[],
         });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[mesh.get_vertex_buffer_layout()],
-            },
-            fragment: Some(wgpu::FragmentState { 
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1, 
-                mask: !0, 
-                alpha_to_coverage_enabled: false, 
-            },
-            multiview: None,
+        let render_pipeline = create_pipeline(
+            device, 
+            config, 
+            "Render Pipeline", 
+            &render_pipeline_layout, 
+            &shader, 
+            &[mesh.get_vertex_buffer_layout()], 
+            wgpu::BlendState::REPLACE);
+
+        let sdf_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sdf Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("sdf.wgsl").into()),
         });
-        RenderPipeline{ mesh, render_pipeline, camera}
+        let sdf_pipeline = create_pipeline(
+            device, 
+            config, 
+            "Sdf Pipeline", 
+            &render_pipeline_layout, 
+            &sdf_shader, 
+            &[mesh.get_instance_buffer_layout()], 
+            wgpu::BlendState::ALPHA_BLENDING);
+        RenderPipeline{ mesh, minimap_mesh, render_pipeline, sdf_pipeline, camera, minimap_camera, minimap_viewport:None,
+            scale_factor:1.0, physical_size:(config.width as f32, config.height as f32)}
     }
 
-    pub fn resize(&mut self, queue:&wgpu::Queue, width:f32, height:f32){
+    // width and height are in logical pixels, the surface itself is sized in physical pixels.
+    pub fn resize(&mut self, queue:&wgpu::Queue, width:f32, height:f32, scale_factor:f32){
+        self.scale_factor = scale_factor;
+        self.physical_size = (width*scale_factor, height*scale_factor);
         self.camera.set_size(queue, width, height);
     }
 
+    pub fn set_view(&mut self, queue:&wgpu::Queue, view:(f32,f32,f32,f32)){
+        let (x, y, width, height) = view;
+        self.camera.set_rect(queue, x, y, width, height);
+    }
+
+    // The minimap viewport is given in logical pixels like the rest of the screen space coordinates.
+    pub fn set_minimap(&mut self, queue:&wgpu::Queue, minimap:Option<MinimapView>){
+        self.minimap_viewport = None;
+        if let Some(minimap) = minimap {
+            let (x, y, width, height) = minimap.view;
+            self.minimap_camera.set_rect(queue, x, y, width, height);
+            self.minimap_viewport = Some(minimap.viewport);
+        }
+    }
+
+    pub fn update_queue(&mut self, queue:&wgpu::Queue){
+        self.mesh.update_queue(queue);
+        self.minimap_mesh.update_queue(queue);
+    }
+
+    // Draws the layers in order, so sdf quads are blended over the triangles added before them and under
+    // the ones added after them, which lets them work for shadows and outlines.
+    fn draw_mesh<'a>(&'a self, render_pass:&mut wgpu::RenderPass<'a>, mesh:&'a Mesh, camera:&'a OrthoCamera){
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        let mut drawn = Layer { indices: 0, sdf_quads: 0 };
+        for layer in &mesh.layers{
+            if layer.indices > drawn.indices {
+                render_pass.set_pipeline(&self.render_pipeline);
+                render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                render_pass.draw_indexed(drawn.indices as u32..layer.indices as u32, 0, 0..1);
+            }
+            if layer.sdf_quads > drawn.sdf_quads {
+                render_pass.set_pipeline(&self.sdf_pipeline);
+                render_pass.set_vertex_buffer(0, mesh.instance_buffer.slice(..));
+                render_pass.draw(0..6, drawn.sdf_quads as u32..layer.sdf_quads as u32);
+            }
+            drawn = *layer;
+        }
+    }
+
     pub fn render(
         &self,
         surface:&wgpu::Surface, 
@@ -102,11 +188,16 @@ impl RenderPipeline{
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.camera.bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.mesh.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.mesh.num_indices, 0, 0..1);
+            self.draw_mesh(&mut render_pass, &self.mesh, &self.camera);
+
+            if let Some((x, y, width, height)) = self.minimap_viewport {
+                let x = (x*self.scale_factor).max(0.0);
+                let y = (y*self.scale_factor).max(0.0);
+                let width = (width*self.scale_factor).min(self.physical_size.0 - x);
+                let height = (height*self.scale_factor).min(self.physical_size.1 - y);
+                render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                self.draw_mesh(&mut render_pass, &self.minimap_mesh, &self.minimap_camera);
+            }
         }
         queue.submit(std::iter::once(encoder.finish()));
         output.present();
@@ -0,0 +1,71 @@
+struct CameraUniform {
+    view: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct InstanceInput {
+    @location(0) rect: vec4<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) params: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) local_position: vec2<f32>,
+    @location(1) half_size: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) params: vec4<f32>,
+};
+
+// Distance to the edge of a rounded box centered on the origin, negative inside.
+fn rounded_box(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + vec2<f32>(radius, radius);
+    return length(max(q, vec2<f32>(0.0, 0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let half_size = instance.rect.zw * 0.5;
+    let center = instance.rect.xy + half_size;
+    // Grow the quad by the blur plus a little for the anti aliased edge.
+    let padding = instance.params.z + 2.0;
+    let local_position = corners[vertex_index] * (half_size + vec2<f32>(padding, padding));
+
+    var out: VertexOutput;
+    out.clip_position = camera.view * vec4<f32>(center + local_position, 0.0, 1.0);
+    out.local_position = local_position;
+    out.half_size = half_size;
+    out.color = instance.color;
+    out.params = instance.params;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let radius = in.params.x;
+    let border_width = in.params.y;
+    let blur = in.params.z;
+    let distance = rounded_box(in.local_position, in.half_size, radius);
+    let pixel = max(fwidth(distance), 0.0001);
+
+    var coverage = clamp(0.5 - distance / pixel, 0.0, 1.0);
+    if blur > 0.0 {
+        coverage = 1.0 - smoothstep(-blur, blur, distance);
+    }
+    if border_width > 0.0 {
+        coverage = coverage * (1.0 - clamp(0.5 - (distance + border_width) / pixel, 0.0, 1.0));
+    }
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
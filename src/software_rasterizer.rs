@@ -0,0 +1,260 @@
+use std::fs::File;
+use std::io::prelude::*;
+use crate::mesh::{Geometry, Layer, SdfQuad};
+use crate::ortho_camera::view_matrix;
+
+// Matches the clear color of the wgpu render pass.
+const CLEAR_COLOR:[f32; 3] = [0.8, 0.8, 0.8];
+
+pub struct Image{
+    pub width:usize,
+    pub height:usize,
+    pub pixels:Vec<[u8; 4]>,
+}
+
+fn linear_to_srgb(value:f32) -> u8{
+    let value = value.clamp(0.0, 1.0);
+    let srgb = if value <= 0.0031308 {
+        value * 12.92
+    }
+    else{
+        1.055 * value.powf(1.0/2.4) - 0.055
+    };
+    (srgb * 255.0).round() as u8
+}
+
+fn srgb_to_linear(value:u8) -> f32{
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    }
+    else{
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn smoothstep(edge0:f32, edge1:f32, x:f32) -> f32{
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// Same distance function as sdf.wgsl.
+fn rounded_box(p:[f32; 2], half_size:[f32; 2], radius:f32) -> f32{
+    let q = [p[0].abs() - half_size[0] + radius, p[1].abs() - half_size[1] + radius];
+    let outside = (q[0].max(0.0).powi(2) + q[1].max(0.0).powi(2)).sqrt();
+    outside + q[0].max(q[1]).min(0.0) - radius
+}
+
+fn edge(a:[f32; 2], b:[f32; 2], p:[f32; 2]) -> f32{
+    (b[0]-a[0])*(p[1]-a[1]) - (b[1]-a[1])*(p[0]-a[0])
+}
+
+impl Image{
+    pub fn new(width:usize, height:usize) -> Self{
+        let clear = [linear_to_srgb(CLEAR_COLOR[0]), linear_to_srgb(CLEAR_COLOR[1]), linear_to_srgb(CLEAR_COLOR[2]), 255];
+        Image { width, height, pixels: vec![clear; width*height] }
+    }
+
+    // Draws the geometry the same way the render pipeline does: the view rect is mapped onto the viewport
+    // and layer by layer the triangles are drawn and then the sdf quads are blended on top of them.
+    pub fn rasterize(&mut self, geometry:&Geometry, view:(f32,f32,f32,f32), viewport:(f32,f32,f32,f32)){
+        let mut drawn = Layer { indices: 0, sdf_quads: 0 };
+        for layer in geometry.layers(){
+            self.rasterize_triangles(geometry, &geometry.indices()[drawn.indices..layer.indices], view, viewport);
+            self.rasterize_sdf_quads(&geometry.sdf_quads()[drawn.sdf_quads..layer.sdf_quads], view, viewport);
+            drawn = layer;
+        }
+    }
+
+    // Back faces are culled and colors are written as sRGB like the surface format does.
+    fn rasterize_triangles(&mut self, geometry:&Geometry, indices:&[u16], view:(f32,f32,f32,f32), viewport:(f32,f32,f32,f32)){
+        let matrix = view_matrix(view.0, view.1, view.2, view.3);
+        let (viewport_x, viewport_y, viewport_width, viewport_height) = viewport;
+        let vertices = geometry.vertices();
+        let to_pixels = |index:u16| -> ([f32; 2], [f32; 3]){
+            let vertex = vertices[index as usize];
+            let clip = matrix * cgmath::Vector4::new(vertex.position[0], vertex.position[1], 0.0, 1.0);
+            let ndc_x = clip.x / clip.w;
+            let ndc_y = clip.y / clip.w;
+            let x = viewport_x + (ndc_x + 1.0) / 2.0 * viewport_width;
+            let y = viewport_y + (1.0 - ndc_y) / 2.0 * viewport_height;
+            ([x, y], vertex.color)
+        };
+        let min_x = viewport_x.max(0.0);
+        let min_y = viewport_y.max(0.0);
+        let max_x = (viewport_x + viewport_width).min(self.width as f32);
+        let max_y = (viewport_y + viewport_height).min(self.height as f32);
+
+        for triangle in indices.chunks_exact(3){
+            let (a, color_a) = to_pixels(triangle[0]);
+            let (b, color_b) = to_pixels(triangle[1]);
+            let (c, color_c) = to_pixels(triangle[2]);
+            // Pixel space has y pointing down, so counter clockwise front faces have a negative area here.
+            let area = edge(a, b, c);
+            if area >= 0.0 {
+                continue;
+            }
+            let start_x = a[0].min(b[0]).min(c[0]).max(min_x).floor() as usize;
+            let start_y = a[1].min(b[1]).min(c[1]).max(min_y).floor() as usize;
+            let end_x = a[0].max(b[0]).max(c[0]).min(max_x).ceil().max(0.0) as usize;
+            let end_y = a[1].max(b[1]).max(c[1]).min(max_y).ceil().max(0.0) as usize;
+            for y in start_y..end_y{
+                for x in start_x..end_x{
+                    let p = [x as f32 + 0.5, y as f32 + 0.5];
+                    let w_a = edge(b, c, p) / area;
+                    let w_b = edge(c, a, p) / area;
+                    let w_c = edge(a, b, p) / area;
+                    if w_a < 0.0 || w_b < 0.0 || w_c < 0.0 {
+                        continue;
+                    }
+                    let mut color = [0u8; 4];
+                    for i in 0..3{
+                        color[i] = linear_to_srgb(color_a[i]*w_a + color_b[i]*w_b + color_c[i]*w_c);
+                    }
+                    color[3] = 255;
+                    self.pixels[y*self.width + x] = color;
+                }
+            }
+        }
+    }
+
+    // Evaluates the same coverage as sdf.wgsl per pixel and blends in linear space.
+    fn rasterize_sdf_quads(&mut self, quads:&[SdfQuad], view:(f32,f32,f32,f32), viewport:(f32,f32,f32,f32)){
+        let (view_x, view_y, view_width, view_height) = view;
+        let (viewport_x, viewport_y, viewport_width, viewport_height) = viewport;
+        let pixel_x = view_width / viewport_width;
+        let pixel_y = view_height / viewport_height;
+        let pixel = pixel_x.max(pixel_y);
+        let min_x = viewport_x.max(0.0);
+        let min_y = viewport_y.max(0.0);
+        let max_x = (viewport_x + viewport_width).min(self.width as f32);
+        let max_y = (viewport_y + viewport_height).min(self.height as f32);
+
+        for quad in quads{
+            let [x, y, width, height] = quad.rect;
+            let [corner_radius, border_width, blur, _] = quad.params;
+            let padding = blur + 2.0;
+            let half_size = [width / 2.0, height / 2.0];
+            let center = [x + half_size[0], y + half_size[1]];
+            let start_x = (viewport_x + (x - padding - view_x) / pixel_x).max(min_x).floor() as usize;
+            let start_y = (viewport_y + (y - padding - view_y) / pixel_y).max(min_y).floor() as usize;
+            let end_x = (viewport_x + (x + width + padding - view_x) / pixel_x).min(max_x).ceil().max(0.0) as usize;
+            let end_y = (viewport_y + (y + height + padding - view_y) / pixel_y).min(max_y).ceil().max(0.0) as usize;
+            for py in start_y..end_y{
+                for px in start_x..end_x{
+                    let world_x = view_x + (px as f32 + 0.5 - viewport_x) * pixel_x;
+                    let world_y = view_y + (py as f32 + 0.5 - viewport_y) * pixel_y;
+                    let distance = rounded_box([world_x - center[0], world_y - center[1]], half_size, corner_radius);
+                    let mut coverage = (0.5 - distance / pixel).clamp(0.0, 1.0);
+                    if blur > 0.0 {
+                        coverage = 1.0 - smoothstep(-blur, blur, distance);
+                    }
+                    if border_width > 0.0 {
+                        coverage *= 1.0 - (0.5 - (distance + border_width) / pixel).clamp(0.0, 1.0);
+                    }
+                    let alpha = quad.color[3] * coverage;
+                    if alpha <= 0.0 {
+                        continue;
+                    }
+                    let destination = &mut self.pixels[py*self.width + px];
+                    for (channel, color) in destination.iter_mut().zip(quad.color.iter()).take(3){
+                        *channel = linear_to_srgb(color * alpha + srgb_to_linear(*channel) * (1.0 - alpha));
+                    }
+                }
+            }
+        }
+    }
+
+    // Writes a binary PPM, which drops the alpha channel.
+    pub fn save_ppm(&self, path:&str) -> std::io::Result<()>{
+        let mut file = File::create(path)?;
+        file.write_all(format!("P6\n{} {}\n255\n", self.width, self.height).as_bytes())?;
+        let mut data = Vec::with_capacity(self.width*self.height*3);
+        for pixel in &self.pixels{
+            data.extend_from_slice(&pixel[..3]);
+        }
+        file.write_all(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED:[u8; 4] = [255, 0, 0, 255];
+    const GREEN:[u8; 4] = [0, 255, 0, 255];
+    const BLUE:[u8; 4] = [0, 0, 255, 255];
+
+    fn pixel(image:&Image, x:usize, y:usize) -> [u8; 4]{
+        image.pixels[y*image.width + x]
+    }
+
+    // A rect, a triangle and a circle in a 100x100 view drawn one to one onto a 100x100 image.
+    fn scene() -> Image{
+        let mut geometry = Geometry::new(64);
+        geometry.add_rect(10.0, 10.0, 30.0, 20.0, 1.0, 0.0, 0.0);
+        geometry.add_polygon(&[[60.0, 10.0], [90.0, 10.0], [60.0, 40.0]], 0.0, 1.0, 0.0);
+        geometry.add_sdf_quad(50.0, 60.0, 30.0, 30.0, [0.0, 0.0, 1.0, 1.0], 15.0, 0.0, 0.0);
+        let mut image = Image::new(100, 100);
+        image.rasterize(&geometry, (0.0, 0.0, 100.0, 100.0), (0.0, 0.0, 100.0, 100.0));
+        image
+    }
+
+    #[test]
+    fn rect_covers_its_pixels(){
+        let image = scene();
+        let clear = Image::new(1, 1).pixels[0];
+        assert_eq!(pixel(&image, 10, 10), RED);
+        assert_eq!(pixel(&image, 39, 29), RED);
+        assert_eq!(pixel(&image, 40, 20), clear);
+        assert_eq!(pixel(&image, 20, 30), clear);
+        assert_eq!(pixel(&image, 5, 5), clear);
+    }
+
+    #[test]
+    fn triangle_covers_only_its_half_of_the_bounds(){
+        let image = scene();
+        let clear = Image::new(1, 1).pixels[0];
+        assert_eq!(pixel(&image, 61, 11), GREEN);
+        assert_eq!(pixel(&image, 70, 25), GREEN);
+        assert_eq!(pixel(&image, 88, 38), clear);
+    }
+
+    #[test]
+    fn sdf_quad_draws_a_circle(){
+        let image = scene();
+        let clear = Image::new(1, 1).pixels[0];
+        assert_eq!(pixel(&image, 65, 75), BLUE);
+        assert_eq!(pixel(&image, 51, 75), BLUE);
+        assert_eq!(pixel(&image, 51, 61), clear);
+        assert_eq!(pixel(&image, 78, 88), clear);
+    }
+
+    #[test]
+    fn view_is_mapped_onto_the_viewport(){
+        let mut geometry = Geometry::new(16);
+        geometry.add_rect(10.0, 10.0, 30.0, 20.0, 1.0, 0.0, 0.0);
+        let mut image = Image::new(100, 100);
+        // The view is twice the size of the viewport and offset by it, so everything shrinks and moves.
+        image.rasterize(&geometry, (0.0, 0.0, 100.0, 100.0), (50.0, 50.0, 50.0, 50.0));
+        let clear = Image::new(1, 1).pixels[0];
+        assert_eq!(pixel(&image, 56, 56), RED);
+        assert_eq!(pixel(&image, 69, 64), RED);
+        assert_eq!(pixel(&image, 20, 20), clear);
+    }
+
+    #[test]
+    fn sdf_quads_are_drawn_in_order_with_the_triangles(){
+        let mut geometry = Geometry::new(64);
+        geometry.add_rect(0.0, 0.0, 50.0, 100.0, 1.0, 0.0, 0.0);
+        geometry.add_sdf_quad(20.0, 20.0, 60.0, 60.0, [0.0, 0.0, 1.0, 1.0], 0.0, 0.0, 0.0);
+        geometry.add_rect(50.0, 0.0, 50.0, 100.0, 0.0, 1.0, 0.0);
+        assert_eq!(geometry.layers().count(), 2);
+        let mut image = Image::new(100, 100);
+        image.rasterize(&geometry, (0.0, 0.0, 100.0, 100.0), (0.0, 0.0, 100.0, 100.0));
+        // Over the rect added before it, under the one added after it.
+        assert_eq!(pixel(&image, 30, 50), BLUE);
+        assert_eq!(pixel(&image, 10, 50), RED);
+        assert_eq!(pixel(&image, 70, 50), GREEN);
+    }
+}
@@ -1,6 +1,34 @@
 use serde::{Serialize, Deserialize};
 use std::fs::File;
 use std::io::prelude::*;
+use crate::mesh::Geometry;
+
+mod minimap;
+mod spatial_grid;
+mod polygon;
+mod platform;
+mod liquid;
+mod trigger;
+mod rigid_body;
+mod joint;
+mod determinism;
+mod query;
+mod portal;
+mod force_field;
+mod gravity;
+mod movement;
+use spatial_grid::SpatialGrid;
+use platform::Path;
+use liquid::{Liquid, LIQUID_WATER, LIQUID_LAVA, LIQUID_ALPHA};
+use trigger::{Trigger, TriggerEvent};
+use rigid_body::RigidBody;
+use joint::Joint;
+use determinism::{Replay, sin_cos};
+use query::QueryFilter;
+use portal::{Portal, upgrade_portals};
+use force_field::ForceField;
+use movement::{MovementConfig, MovementState};
+pub use minimap::MinimapView;
 
 struct Drag{
     dragging:bool,
@@ -13,8 +41,13 @@ struct Drag{
 #[derive(Serialize, Deserialize)]
 enum CollisionType{
     Bounce,
+    // Only found in old saves, loading turns it into a Portal.
     PortalTo,
     None,
+    // Only blocks objects falling onto it from above, so it can be jumped through from below.
+    OneWay,
+    // Doesn't block anything, the player can climb it with W and S.
+    Ladder,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -23,6 +56,8 @@ enum Controller{
     Player,
     AI,
     FollowTarget,
+    // Kinematic, follows its path and carries whatever stands on it.
+    Platform,
 }
 
 enum Mode{
@@ -38,7 +73,7 @@ struct Color{
 }
 
 
-#[derive(Serialize, Deserialize, Copy, Clone)]
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Default)]
 pub struct Vector2{
     pub x:f32,
     pub y:f32,
@@ -60,6 +95,22 @@ impl Vector2{
     fn scale(&self, scale_x:f32, scale_y:f32) -> Vector2{
         Vector2 { x: self.x*scale_x, y: self.y*scale_y }
     }
+
+    fn mul(&self, value:f32) -> Vector2{
+        Vector2 { x: self.x*value, y: self.y*value }
+    }
+
+    fn add(&self, other:Vector2) -> Vector2{
+        Vector2 { x: self.x+other.x, y: self.y+other.y }
+    }
+
+    fn sub(&self, other:Vector2) -> Vector2{
+        Vector2 { x: self.x-other.x, y: self.y-other.y }
+    }
+
+    fn dot(&self, other:Vector2) -> f32{
+        self.x*other.x + self.y*other.y
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
@@ -71,9 +122,19 @@ struct Rect{
 }
 
 impl Rect{
-    /*fn expand(&self, radius:f32) -> Rect{
+    fn expand(&self, radius:f32) -> Rect{
         Rect { x: self.x-radius, y: self.y-radius, width: self.width+radius*2.0, height: self.height+radius*2.0 }
-    }*/
+    }
+
+    fn union(a:&Rect, b:&Rect) -> Rect{
+        let x = a.x.min(b.x);
+        let y = a.y.min(b.y);
+        Rect { x, y, width: (a.x+a.width).max(b.x+b.width) - x, height: (a.y+a.height).max(b.y+b.height) - y }
+    }
+
+    fn contains_rect(&self, other:&Rect) -> bool{
+        other.x >= self.x && other.y >= self.y && other.x+other.width <= self.x+self.width && other.y+other.height <= self.y+self.height
+    }
 
     fn contains(&self, point:Vector2) -> bool{
         point.x > self.x && point.y > self.y && point.x < self.x+self.width && point.y< self.y+self.height
@@ -83,6 +144,58 @@ impl Rect{
         Vector2{x:self.x+self.width/2.0, y:self.y+self.height/2.0}
     }
 
+    // How far the rect reaches from its center along the unit direction.
+    fn extent(&self, direction:Vector2) -> f32{
+        direction.x.abs() * self.width / 2.0 + direction.y.abs() * self.height / 2.0
+    }
+
+    // Swept AABB test of this rect moving by motion against a static rect. Returns the time of impact
+    // as a fraction of motion and the contact normal. Rects that already overlap only stop motion that
+    // goes deeper along the axis they overlap least on, so objects can always move out of something
+    // they got stuck in but never sink further into it.
+    fn sweep(&self, motion:Vector2, other:&Rect, other_id:usize) -> Option<Hit>{
+        if Rect::overlaps(self, other) {
+            let pushes = [
+                (self.x + self.width - other.x, Vector2 { x: -1.0, y: 0.0 }),
+                (other.x + other.width - self.x, Vector2 { x: 1.0, y: 0.0 }),
+                (self.y + self.height - other.y, Vector2 { x: 0.0, y: -1.0 }),
+                (other.y + other.height - self.y, Vector2 { x: 0.0, y: 1.0 }),
+            ];
+            let (_, normal) = pushes.into_iter().min_by(|a, b| a.0.total_cmp(&b.0)).unwrap();
+            if motion.dot(normal) < 0.0 {
+                return Some(Hit { time: 0.0, normal, other_id });
+            }
+            return None;
+        }
+        let axis = |position:f32, size:f32, other_position:f32, other_size:f32, motion:f32| -> Option<(f32, f32)>{
+            if motion == 0.0 {
+                if position < other_position + other_size && other_position < position + size {
+                    return Some((f32::NEG_INFINITY, f32::INFINITY));
+                }
+                return None;
+            }
+            let (entry, exit) = if motion > 0.0 {
+                (other_position - (position + size), other_position + other_size - position)
+            } else {
+                (other_position + other_size - position, other_position - (position + size))
+            };
+            Some((entry / motion, exit / motion))
+        };
+        let (entry_x, exit_x) = axis(self.x, self.width, other.x, other.width, motion.x)?;
+        let (entry_y, exit_y) = axis(self.y, self.height, other.y, other.height, motion.y)?;
+        let entry = entry_x.max(entry_y);
+        let exit = exit_x.min(exit_y);
+        if entry > exit || !(0.0..=1.0).contains(&entry) {
+            return None;
+        }
+        let normal = if entry_x > entry_y {
+            Vector2 { x: -motion.x.signum(), y: 0.0 }
+        } else {
+            Vector2 { x: 0.0, y: -motion.y.signum() }
+        };
+        Some(Hit { time: entry, normal, other_id })
+    }
+
     fn overlaps(a:&Rect, b:&Rect) -> bool{
         let center_a = a.center();
         let center_b = b.center();
@@ -94,6 +207,80 @@ impl Rect{
     }
 }
 
+// How long an object ignores one way platforms after Down+Jump.
+const DROP_THROUGH_FRAMES:usize = 15;
+
+struct Hit{
+    time:f32,
+    normal:Vector2,
+    other_id:usize,
+}
+
+// Distance kept between touching objects so float rounding can't push them into each other.
+const CONTACT_SKIN:f32 = 0.01;
+
+// The static batch is built for the view plus this margin so it only has to be rebuilt
+// once the camera has moved that far.
+const STATIC_CULL_MARGIN:f32 = 400.0;
+const GRID_CELL_SIZE:f32 = 128.0;
+
+// KeyC in the editor cycles an object between square, rounded and fully round corners.
+// The radius is clamped to half the object size when drawn, so a square object becomes a circle.
+const CORNER_RADIUS_SMALL:f32 = 10.0;
+const CORNER_RADIUS_ROUND:f32 = 1.0e6;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+enum Shape{
+    #[default]
+    Rect,
+    // Convex polygon with points relative to the top left of the rect, which stays its bounding box.
+    Polygon(Vec<Vector2>),
+}
+
+// Restitution is how much of the velocity into a contact bounces back, the highest of the two objects is used.
+// Friction multiplies between the two objects and is how quickly grounded objects reach their target speed,
+// above 1 it also slows walking down. Air drag takes a fraction of the velocity away every frame.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+struct Material{
+    restitution:f32,
+    friction:f32,
+    air_drag:f32,
+}
+
+impl Default for Material{
+    fn default() -> Self{
+        Material { restitution: 0.0, friction: 1.0, air_drag: 0.0 }
+    }
+}
+
+// KeyF in the editor cycles an object through these.
+const MATERIAL_ICE:Material = Material { restitution: 0.0, friction: 0.05, air_drag: 0.0 };
+const MATERIAL_STICKY:Material = Material { restitution: 0.0, friction: 3.0, air_drag: 0.0 };
+const MATERIAL_TRAMPOLINE:Material = Material { restitution: 1.0, friction: 1.0, air_drag: 0.0 };
+// Bounces slower than this come to rest, so resting objects don't jitter under gravity.
+const MIN_BOUNCE_SPEED:f32 = 1.0;
+
+// Tunables of the world that aren't about how characters move, see MovementConfig for those.
+// Can be loaded from a JSON file, anything left out of it keeps its default.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+struct PhysicsConfig{
+    // The fastest anything falls, in pixels per frame.
+    terminal_velocity:f32,
+    // The steepest slope in degrees that can be stood on and walked up.
+    max_slope_angle:f32,
+    // How long knockback keeps a hit object from steering.
+    hitstun_frames:usize,
+    // How long a hit object can't be hurt again.
+    invulnerability_frames:usize,
+}
+
+impl Default for PhysicsConfig{
+    fn default() -> Self{
+        PhysicsConfig { terminal_velocity: 20.0, max_slope_angle: 50.0, hitstun_frames: 12, invulnerability_frames: 45 }
+    }
+}
+
 const FACTION_PLAYER:u32 = 1;
 const FACTION_ENEMY:u32 = 2;
 
@@ -115,6 +302,172 @@ struct Object{
     max_health:i32,
     damage:i32,
     disable_damage_bar_at_frame:usize,
+    #[serde(default)]
+    corner_radius:f32,
+    #[serde(default)]
+    shape:Shape,
+    #[serde(default)]
+    path:Option<Path>,
+    #[serde(default)]
+    material:Material,
+    #[serde(default)]
+    liquid:Option<Liquid>,
+    #[serde(default)]
+    trigger:Option<Trigger>,
+    #[serde(default)]
+    body:Option<RigidBody>,
+    #[serde(default)]
+    joints:Vec<Joint>,
+    // How hard this object's hits push the target away.
+    #[serde(default)]
+    knockback:f32,
+    #[serde(default)]
+    portal:Option<Portal>,
+    #[serde(default)]
+    force:Option<ForceField>,
+    // The size to stand back up to while crouching. Saved so a level saved mid-crouch can still stand up.
+    #[serde(default)]
+    standing_size:Option<Vector2>,
+    #[serde(skip)]
+    grounded:bool,
+    #[serde(skip)]
+    drop_through_until_frame:usize,
+    #[serde(skip)]
+    climbing:bool,
+    #[serde(skip)]
+    swim_stroke_at_frame:usize,
+    #[serde(skip)]
+    hitstun_until_frame:usize,
+    #[serde(skip)]
+    invulnerable_until_frame:usize,
+    #[serde(skip)]
+    portal_cooldown_until_frame:usize,
+    // The gravity the object was last pulled by, without its own gravity scale.
+    #[serde(skip)]
+    gravity_direction:Vector2,
+    #[serde(skip)]
+    movement:MovementState,
+}
+
+impl Object{
+    // A still, solid rect with nothing else set, to be filled in with struct update syntax.
+    fn new(rect:Rect, color:Color) -> Self{
+        Object {
+            faction: 0,
+            controller: Controller::None,
+            rect,
+            color,
+            velocity: Vector2 { x: 0.0, y: 0.0 },
+            gravity: 0.0,
+            direction: Vector2 { x: 0.0, y: 0.0 },
+            collision_type: CollisionType::Bounce,
+            destroying: false,
+            destroy_at_frame: 0,
+            enable_firing_at_frame: 0,
+            target: 0,
+            health: 0,
+            max_health: 0,
+            damage: 0,
+            disable_damage_bar_at_frame: 0,
+            corner_radius: 0.0,
+            shape: Shape::Rect,
+            path: None,
+            material: Material::default(),
+            liquid: None,
+            trigger: None,
+            body: None,
+            joints: Vec::new(),
+            knockback: 0.0,
+            portal: None,
+            force: None,
+            standing_size: None,
+            grounded: false,
+            drop_through_until_frame: 0,
+            climbing: false,
+            swim_stroke_at_frame: 0,
+            hitstun_until_frame: 0,
+            invulnerable_until_frame: 0,
+            portal_cooldown_until_frame: 0,
+            gravity_direction: Vector2 { x: 0.0, y: 0.0 },
+            movement: MovementState::default(),
+        }
+    }
+
+    // Static objects never move on their own, so they are drawn from the cached static batch.
+    fn is_static(&self) -> bool{
+        matches!(self.controller, Controller::None) && self.gravity == 0.0 && self.body.is_none()
+    }
+
+    fn is_polygon(&self) -> bool{
+        matches!(self.shape, Shape::Polygon(_)) || self.body.is_some()
+    }
+
+    // The collision shape in world coordinates.
+    fn polygon(&self) -> Vec<Vector2>{
+        let rect = &self.rect;
+        if let Some(body) = &self.body {
+            return body.corners(rect.center());
+        }
+        match &self.shape {
+            Shape::Rect => vec![
+                Vector2 { x: rect.x, y: rect.y },
+                Vector2 { x: rect.x + rect.width, y: rect.y },
+                Vector2 { x: rect.x + rect.width, y: rect.y + rect.height },
+                Vector2 { x: rect.x, y: rect.y + rect.height },
+            ],
+            Shape::Polygon(points) => points.iter().map(|p| Vector2 { x: rect.x + p.x, y: rect.y + p.y }).collect(),
+        }
+    }
+
+    // Liquids and force zones are see-through.
+    fn is_translucent(&self) -> bool{
+        self.liquid.is_some() || self.force.is_some_and(|force| force.is_zone())
+    }
+
+    // Rounded and see-through objects and triggers are drawn as sdf quads, which the static batch leaves out.
+    fn uses_sdf(&self) -> bool{
+        self.trigger.is_some() || (!self.is_polygon() && (self.corner_radius > 0.0 || self.is_translucent()))
+    }
+
+    fn draw(&self, geometry:&mut Geometry){
+        if self.trigger.is_some() {
+            geometry.add_sdf_quad(
+                self.rect.x, 
+                self.rect.y, 
+                self.rect.width, 
+                self.rect.height, 
+                [self.color.r, self.color.g, self.color.b, 0.8], 
+                0.0, 
+                3.0, 
+                0.0);
+        }
+        else if self.is_polygon() {
+            let points:Vec<[f32; 2]> = self.polygon().iter().map(|p| [p.x, p.y]).collect();
+            geometry.add_polygon(&points, self.color.r, self.color.g, self.color.b);
+        }
+        else if self.uses_sdf() {
+            let alpha = if self.is_translucent() { LIQUID_ALPHA } else { 1.0 };
+            geometry.add_sdf_quad(
+                self.rect.x, 
+                self.rect.y, 
+                self.rect.width, 
+                self.rect.height, 
+                [self.color.r, self.color.g, self.color.b, alpha], 
+                self.corner_radius, 
+                0.0, 
+                0.0);
+        }
+        else{
+            geometry.add_rect(
+                self.rect.x, 
+                self.rect.y, 
+                self.rect.width, 
+                self.rect.height, 
+                self.color.r, 
+                self.color.g, 
+                self.color.b);
+        }
+    }
 }
 
 struct Input{
@@ -141,19 +494,38 @@ impl Input{
     }
 }
 
+// The velocity along a contact normal after bouncing off it.
+fn bounce(velocity:f32, restitution:f32) -> f32{
+    let rebound = -velocity * restitution;
+    if rebound.abs() < MIN_BOUNCE_SPEED { 0.0 } else { rebound }
+}
+
 pub struct VectorGraphics {
     objects:Vec<Object>,
     drag:Drag,
     mouse_position:Vector2,
     mode:Mode,
-    speed:f32,
+    movement:MovementConfig,
     editor_speed:f32,
-    jump_force:f32,
-    last_portal_in:usize,
+    // Pulls objects by their own gravity scale, gravity zones override it.
+    gravity:Vector2,
+    physics:PhysicsConfig,
+    last_portal:Option<usize>,
+    last_platform:Option<usize>,
+    last_trigger:Option<usize>,
+    trigger_events:Vec<TriggerEvent>,
+    unread_trigger_events:Vec<TriggerEvent>,
+    messages:Vec<String>,
+    joint_start:Option<(usize, Vector2)>,
+    recording:Option<Replay>,
+    replay:Option<(Replay, usize)>,
     cam:Vector2,
     screen:Vector2,
     frame:usize,
     input:Input,
+    show_minimap:bool,
+    static_version:usize,
+    grid:SpatialGrid,
 }
 
 impl VectorGraphics {
@@ -167,7 +539,17 @@ impl VectorGraphics {
         let mut file = File::open("save.txt").unwrap();
         let mut contents = String::new();
         file.read_to_string(&mut contents).unwrap();
-        serde_json::from_str(&contents).unwrap()
+        let mut objects:Vec<Object> = serde_json::from_str(&contents).unwrap();
+        upgrade_portals(&mut objects);
+        objects
+    }
+
+    // Replaces the physics tunables with the ones in a JSON file.
+    pub fn load_physics(&mut self, path:&str) -> std::io::Result<()>{
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        self.physics = serde_json::from_str(&contents)?;
+        Ok(())
     }
 
     fn abs_rect(x:f32, y:f32, w:f32, h:f32) -> (f32, f32, f32, f32){
@@ -187,29 +569,43 @@ impl VectorGraphics {
     }
 
     fn find_object_at_point(&self, point:Vector2) -> Option<usize>{
-        for i in 0..self.objects.len(){
-            if self.objects[i].rect.contains(point){
-                return Some(i);
-            }
-        }
-        None
+        self.objects_at_point(point, QueryFilter::all()).first().copied()
+    }
+
+    pub fn play_level(&mut self){
+        self.objects = self.load();
+        self.static_version += 1;
+        self.rebuild_grid();
+        self.mode = Mode::Play;
     }
 
     pub fn new() -> VectorGraphics{
-        return VectorGraphics { 
+        VectorGraphics { 
             objects:Vec::new(), 
             drag:Drag { dragging: false, x: 0.0, y: 0.0, x2: 0.0, y2: 0.0 }, 
             mouse_position:Vector2{x:0.0, y:0.0},
             mode:Mode::Edit,
-            speed:5.0,
+            movement:MovementConfig::default(),
             editor_speed:7.5,
-            jump_force: 14.0,
+            gravity:Vector2 { x: 0.0, y: 1.0 },
+            physics:PhysicsConfig::default(),
             input:Input::new(),
-            last_portal_in:0,
+            last_portal:None,
+            last_platform:None,
+            last_trigger:None,
+            trigger_events:Vec::new(),
+            unread_trigger_events:Vec::new(),
+            messages:Vec::new(),
+            joint_start:None,
+            recording:None,
+            replay:None,
             cam:Vector2{x:0.0, y:0.0},
             screen:Vector2{x:0.0, y:0.0},
             frame:0,
-         };
+            show_minimap:true,
+            static_version:0,
+            grid:SpatialGrid::new(GRID_CELL_SIZE),
+         }
     }
 
     pub fn mousemove(&mut self, mouse_position:Vector2){
@@ -230,8 +626,12 @@ impl VectorGraphics {
         self.screen.y = screen_y;
     }
 
+    #[allow(clippy::single_match)]
     pub fn keydown(&mut self, key:winit::keyboard::KeyCode){
         self.input.press(key);
+        if key == winit::keyboard::KeyCode::KeyM {
+            self.show_minimap = !self.show_minimap;
+        }
         match  self.mode {
             Mode::Play => {
                 match key {
@@ -252,15 +652,13 @@ impl VectorGraphics {
                             _ => {}
                         }
                     }
-                    winit::keyboard::KeyCode::KeyR=>{
-                        if !self.drag.dragging{
-                            let relative_mouse_position = self.get_relative_mouse_position();
-                            self.drag.x = relative_mouse_position.x;
-                            self.drag.y = relative_mouse_position.y;
-                            self.drag.x2 = relative_mouse_position.x;
-                            self.drag.y2 = relative_mouse_position.y;
-                            self.drag.dragging = true;
-                        }
+                    winit::keyboard::KeyCode::KeyR if !self.drag.dragging=>{
+                        let relative_mouse_position = self.get_relative_mouse_position();
+                        self.drag.x = relative_mouse_position.x;
+                        self.drag.y = relative_mouse_position.y;
+                        self.drag.x2 = relative_mouse_position.x;
+                        self.drag.y2 = relative_mouse_position.y;
+                        self.drag.dragging = true;
                     }
                     winit::keyboard::KeyCode::KeyP=>{
                         match self.find_object_at_point(self.get_relative_mouse_position()){
@@ -269,6 +667,7 @@ impl VectorGraphics {
                                 self.objects[id].controller = Controller::Player;
                                 self.objects[id].color = Color { r:1.0, g:0.5, b:0.0 };
                                 self.objects[id].gravity = 0.3;
+                                self.static_version += 1;
                             }
                             _ => {}
                         }
@@ -283,28 +682,20 @@ impl VectorGraphics {
                                 self.objects[id].direction = Vector2{ x:-1.0, y:0.0 };
                                 self.objects[id].health = 20;
                                 self.objects[id].max_health = 20;
+                                self.static_version += 1;
                             }
                             _ => {}
                         }
                     }
                     winit::keyboard::KeyCode::KeyI=>{
                         match self.find_object_at_point(self.get_relative_mouse_position()){
-                            Some(id) => {
-                                self.objects[id].color = Color{r:0.2, g:1.0, b:0.2};
-                                self.objects[id].collision_type = CollisionType::None;
-                                self.last_portal_in = id;
-                            }
+                            Some(id) => self.edit_portal(id),
                             _ => {}
                         }
                     }
                     winit::keyboard::KeyCode::KeyO => {
                         match self.find_object_at_point(self.get_relative_mouse_position()){
-                            Some(id) => {
-                                self.objects[id].color = Color{r:0.2, g:0.2, b:1.0};
-                                self.objects[id].collision_type = CollisionType::None;
-                                self.objects[self.last_portal_in].collision_type = CollisionType::PortalTo;
-                                self.objects[self.last_portal_in].target = id;
-                            }
+                            Some(id) => self.link_portal(id),
                             _ => {}
                         }
                     }
@@ -314,8 +705,269 @@ impl VectorGraphics {
                     winit::keyboard::KeyCode::KeyS=>{
                         self.save();
                     }
+                    winit::keyboard::KeyCode::KeyC=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => {
+                                let corner_radius = self.objects[id].corner_radius;
+                                self.objects[id].corner_radius = if corner_radius == 0.0 {
+                                    CORNER_RADIUS_SMALL
+                                } else if corner_radius < CORNER_RADIUS_ROUND {
+                                    CORNER_RADIUS_ROUND
+                                } else {
+                                    0.0
+                                };
+                                self.static_version += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyT=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => {
+                                let rect = self.objects[id].rect;
+                                let ramp_right = vec![
+                                    Vector2 { x: 0.0, y: rect.height },
+                                    Vector2 { x: rect.width, y: rect.height },
+                                    Vector2 { x: rect.width, y: 0.0 },
+                                ];
+                                let ramp_left = vec![
+                                    Vector2 { x: 0.0, y: 0.0 },
+                                    Vector2 { x: rect.width, y: rect.height },
+                                    Vector2 { x: 0.0, y: rect.height },
+                                ];
+                                self.objects[id].shape = match &self.objects[id].shape {
+                                    Shape::Rect => Shape::Polygon(ramp_right),
+                                    Shape::Polygon(points) if *points == ramp_right => Shape::Polygon(ramp_left),
+                                    _ => Shape::Rect,
+                                };
+                                self.static_version += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyJ=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => {
+                                match self.objects[id].collision_type {
+                                    CollisionType::OneWay => {
+                                        self.objects[id].color = Color { r: 0.025, g: 0.025, b: 0.025 };
+                                        self.objects[id].collision_type = CollisionType::Bounce;
+                                    }
+                                    _ => {
+                                        self.objects[id].color = Color { r: 0.6, g: 0.4, b: 0.2 };
+                                        self.objects[id].collision_type = CollisionType::OneWay;
+                                    }
+                                }
+                                self.static_version += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                    // KeyN on an object turns it into a moving platform, KeyN on empty space then adds a waypoint
+                    // to the last platform, with the platform centered on the mouse.
+                    winit::keyboard::KeyCode::KeyN=>{
+                        let mouse = self.get_relative_mouse_position();
+                        match self.find_object_at_point(mouse){
+                            Some(id) => {
+                                let rect = self.objects[id].rect;
+                                if self.objects[id].path.is_none() {
+                                    self.objects[id].path = Some(Path::new(Vector2 { x: rect.x, y: rect.y }));
+                                }
+                                self.objects[id].controller = Controller::Platform;
+                                self.objects[id].gravity = 0.0;
+                                self.objects[id].color = Color { r: 0.2, g: 0.3, b: 0.6 };
+                                self.last_platform = Some(id);
+                                self.static_version += 1;
+                            }
+                            None => {
+                                match self.last_platform.and_then(|id| self.objects.get_mut(id)) {
+                                    Some(object) => {
+                                        let waypoint = Vector2 { x: mouse.x - object.rect.width/2.0, y: mouse.y - object.rect.height/2.0 };
+                                        match object.path.as_mut() {
+                                            Some(path) => {
+                                                path.waypoints.push(waypoint);
+                                                path.reset();
+                                            }
+                                            _ => {}
+                                        }
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                    }
+                    // KeyG cycles a platform between looping and ping-pong paths, with and without easing.
+                    winit::keyboard::KeyCode::KeyG=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => {
+                                match self.objects[id].path.as_mut() {
+                                    Some(path) => path.next_style(),
+                                    _ => {}
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyK=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => {
+                                match self.objects[id].collision_type {
+                                    CollisionType::Ladder => {
+                                        self.objects[id].color = Color { r: 0.025, g: 0.025, b: 0.025 };
+                                        self.objects[id].collision_type = CollisionType::Bounce;
+                                    }
+                                    _ => {
+                                        self.objects[id].color = Color { r: 0.3, g: 0.6, b: 0.2 };
+                                        self.objects[id].collision_type = CollisionType::Ladder;
+                                    }
+                                }
+                                self.static_version += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyX=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => {
+                                self.cycle_trigger(id);
+                                self.last_trigger = Some(id);
+                                self.static_version += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                    winit::keyboard::KeyCode::Digit1 | winit::keyboard::KeyCode::Digit2 | winit::keyboard::KeyCode::Digit3=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => self.configure_trigger(id, key),
+                            _ => {}
+                        }
+                    }
+                    // KeyV links the last trigger to the object under the mouse, the door it opens or where it spawns.
+                    winit::keyboard::KeyCode::KeyV=>{
+                        match (self.find_object_at_point(self.get_relative_mouse_position()), self.last_trigger) {
+                            (Some(id), Some(trigger_id)) if id != trigger_id => {
+                                match &mut self.objects[trigger_id].trigger {
+                                    Some(trigger) => trigger.target = Some(id),
+                                    _ => {}
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    // KeyB cycles an object between a fixed block, a crate that tumbles and a seesaw pinned at its center.
+                    winit::keyboard::KeyCode::KeyB=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => {
+                                let object = &mut self.objects[id];
+                                match object.body {
+                                    None => {
+                                        let size = Vector2 { x: object.rect.width, y: object.rect.height };
+                                        object.body = Some(RigidBody::new(size, 1.0, false));
+                                        object.gravity = 0.3;
+                                        object.color = Color { r: 0.6, g: 0.45, b: 0.25 };
+                                    }
+                                    Some(body) if !body.pinned => {
+                                        object.body = Some(RigidBody::new(body.size, 2.0, true));
+                                        object.gravity = 0.0;
+                                        object.color = Color { r: 0.4, g: 0.3, b: 0.2 };
+                                    }
+                                    Some(body) => {
+                                        let center = object.rect.center();
+                                        object.rect = Rect { x: center.x - body.size.x/2.0, y: center.y - body.size.y/2.0, width: body.size.x, height: body.size.y };
+                                        object.body = None;
+                                        object.color = Color { r: 0.025, g: 0.025, b: 0.025 };
+                                    }
+                                }
+                                object.velocity = Vector2 { x: 0.0, y: 0.0 };
+                                self.moved(id);
+                                self.static_version += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyH=>{
+                        self.hook(self.get_relative_mouse_position());
+                    }
+                    // KeyU cycles the last joint of the object under the mouse between rope, spring and hinge, then removes it.
+                    winit::keyboard::KeyCode::KeyU=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => self.cycle_joint(id),
+                            _ => {}
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyZ=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => self.cycle_force(id),
+                            _ => {}
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyY=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => self.turn_force(id),
+                            _ => {}
+                        }
+                    }
+                    winit::keyboard::KeyCode::Equal | winit::keyboard::KeyCode::Minus=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => self.scale_force(id, key == winit::keyboard::KeyCode::Equal),
+                            _ => {}
+                        }
+                    }
+                    // KeyQ cycles an object between solid, water, lava and lava that spares the player.
+                    winit::keyboard::KeyCode::KeyQ=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => {
+                                let object = &mut self.objects[id];
+                                let lava_sparing_player = Liquid { spares: vec![FACTION_PLAYER], ..LIQUID_LAVA };
+                                match &object.liquid {
+                                    None => {
+                                        object.liquid = Some(LIQUID_WATER);
+                                        object.collision_type = CollisionType::None;
+                                        object.color = Color { r: 0.2, g: 0.4, b: 0.9 };
+                                    }
+                                    Some(liquid) if *liquid == LIQUID_WATER => {
+                                        object.liquid = Some(LIQUID_LAVA);
+                                        object.color = Color { r: 1.0, g: 0.35, b: 0.05 };
+                                    }
+                                    Some(liquid) if *liquid == LIQUID_LAVA => {
+                                        object.liquid = Some(lava_sparing_player);
+                                        object.color = Color { r: 1.0, g: 0.6, b: 0.05 };
+                                    }
+                                    Some(_) => {
+                                        object.liquid = None;
+                                        object.collision_type = CollisionType::Bounce;
+                                        object.color = Color { r: 0.025, g: 0.025, b: 0.025 };
+                                    }
+                                }
+                                self.static_version += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                    winit::keyboard::KeyCode::KeyF=>{
+                        match self.find_object_at_point(self.get_relative_mouse_position()){
+                            Some(id) => {
+                                let (material, color) = match self.objects[id].material {
+                                    m if m == MATERIAL_ICE => (MATERIAL_STICKY, Color { r: 0.3, g: 0.5, b: 0.1 }),
+                                    m if m == MATERIAL_STICKY => (MATERIAL_TRAMPOLINE, Color { r: 0.8, g: 0.2, b: 0.8 }),
+                                    m if m == MATERIAL_TRAMPOLINE => (Material::default(), Color { r: 0.025, g: 0.025, b: 0.025 }),
+                                    _ => (MATERIAL_ICE, Color { r: 0.6, g: 0.85, b: 1.0 }),
+                                };
+                                self.objects[id].material = material;
+                                self.objects[id].color = color;
+                                self.static_version += 1;
+                            }
+                            _ => {}
+                        }
+                    }
                     winit::keyboard::KeyCode::KeyL=>{
                         self.objects = self.load();
+                        self.last_portal = None;
+                        self.last_platform = None;
+                        self.last_trigger = None;
+                        self.joint_start = None;
+                        self.static_version += 1;
+                        self.rebuild_grid();
                     }
                     _=>{}
                 }
@@ -325,60 +977,71 @@ impl VectorGraphics {
 
     pub fn keyup(&mut self, key:winit::keyboard::KeyCode){
         self.input.release(&key);
-        match key{
-            winit::keyboard::KeyCode::KeyR=>{
-                if self.drag.dragging{
-                    self.drag.dragging = false;
-                    let abs_rect = Self::abs_rect(self.drag.x, self.drag.y, self.drag.x2 - self.drag.x, self.drag.y2 - self.drag.y);
-                    let rect = Rect {
-                        x: abs_rect.0, 
-                        y: abs_rect.1, 
-                        width: abs_rect.2, 
-                        height: abs_rect.3,
-                    };
-                    self.objects.push(Object { 
-                        controller: Controller::None,
-                        rect: rect, 
-                        color: Color { r: 0.025, g: 0.025, b: 0.025 },
-                        gravity: 0.0,
-                        velocity: Vector2 { x: 0.0, y: 0.0 }, 
-                        direction: Vector2 { x: 0.0, y: 0.0 },
-                        collision_type: CollisionType::Bounce,
-                        destroying: false,
-                        destroy_at_frame: 0,
-                        enable_firing_at_frame: 0,
-                        target:0,
-                        faction:FACTION_PLAYER,
-                        health:0,
-                        max_health:0,
-                        damage:0,
-                        disable_damage_bar_at_frame:0,
-                    });
-                }
-            }
-            _=>{}
+        if key == winit::keyboard::KeyCode::KeyR && self.drag.dragging {
+            self.drag.dragging = false;
+            let abs_rect = Self::abs_rect(self.drag.x, self.drag.y, self.drag.x2 - self.drag.x, self.drag.y2 - self.drag.y);
+            let rect = Rect {
+                x: abs_rect.0, 
+                y: abs_rect.1, 
+                width: abs_rect.2, 
+                height: abs_rect.3,
+            };
+            self.add_object(Object {
+                faction: FACTION_PLAYER,
+                ..Object::new(rect, Color { r: 0.025, g: 0.025, b: 0.025 })
+            });
         }
     }
 
     fn overlaps(&self, object_id:usize)->Vec<usize>{
-        let mut result:Vec<usize> = Vec::new();
-        for i in 0..self.objects.len(){
-            if i!=object_id && Rect::overlaps(&self.objects[object_id].rect, &self.objects[i].rect){
-                result.push(i);
-            }
+        self.overlap_rect(&self.objects[object_id].rect, QueryFilter::all().ignoring(object_id))
+    }
+
+    // Objects can't be hurt again for a while after a hit. Returns whether the damage was taken.
+    fn take_damage(&mut self, id:usize, damage:i32) -> bool{
+        if self.objects[id].max_health <= 0 || self.objects[id].invulnerable_until_frame > self.frame {
+            return false;
+        }
+        self.objects[id].invulnerable_until_frame = self.frame + self.physics.invulnerability_frames;
+        self.lose_health(id, damage);
+        true
+    }
+
+    // Damage that neither checks nor grants invulnerability, for hazards that hurt on their own interval.
+    fn lose_health(&mut self, id:usize, damage:i32){
+        if self.objects[id].max_health <= 0 {
+            return;
+        }
+        self.objects[id].health -= damage;
+        self.objects[id].disable_damage_bar_at_frame = self.frame+120;
+        if self.objects[id].health <= 0{
+            self.objects[id].health = 0;
+            self.objects[id].destroying = true;
+            self.objects[id].destroy_at_frame = self.frame;
         }
-        result
+    }
+
+    // Pushes id away from other_id and slightly up, and ignores its controller for a moment.
+    fn knock_back(&mut self, id:usize, other_id:usize){
+        let strength = self.objects[other_id].knockback;
+        if strength == 0.0 {
+            return;
+        }
+        let away = self.objects[id].rect.center().sub(self.objects[other_id].rect.center()).normalize();
+        let impulse = away.sub(self.objects[id].down().mul(0.5)).normalize().mul(strength);
+        match self.objects[id].body {
+            Some(body) => self.apply_impulse(id, impulse.mul(body.mass), Vector2 { x: 0.0, y: 0.0 }),
+            None => self.objects[id].velocity = impulse,
+        }
+        self.objects[id].hitstun_until_frame = self.frame + self.physics.hitstun_frames;
     }
 
     fn apply_damage_to_id(&mut self, id:usize, other_id:usize){
         if self.objects[id].faction != self.objects[other_id].faction && self.objects[id].max_health>0 && self.objects[other_id].damage != 0{
-            self.objects[id].health -= self.objects[other_id].damage;
-            self.objects[other_id].damage = 0;
-            self.objects[id].disable_damage_bar_at_frame = self.frame+120;
-            if self.objects[id].health <= 0{
-                self.objects[id].health = 0;
-                self.objects[id].destroying = true;
-                self.objects[id].destroy_at_frame = self.frame;
+            let damage = self.objects[other_id].damage;
+            if self.take_damage(id, damage) {
+                self.objects[other_id].damage = 0;
+                self.knock_back(id, other_id);
             }
         }
     }
@@ -388,138 +1051,370 @@ impl VectorGraphics {
         self.apply_damage_to_id(other_id, id);
     }
 
-    fn portal_to(&mut self, id:usize, location:Vector2) -> bool{
-        let old_x = self.objects[id].rect.x;
-        let old_y = self.objects[id].rect.y;
-        self.objects[id].rect.x = location.x - self.objects[id].rect.width/2.0;
-        self.objects[id].rect.y = location.y - self.objects[id].rect.height/2.0;
-        for other_id in self.overlaps(id) {
-            self.apply_damage(id, other_id);
-            match self.objects[other_id].collision_type {
-                CollisionType::Bounce => {
-                    self.objects[id].rect.x = old_x;
-                    self.objects[id].rect.y = old_y;
-                    return false;
-                }
-                _ => {}
+    // One way platforms only block objects moving down that started out above them,
+    // and not while the object is dropping through.
+    fn blocks(&self, id:usize, other_id:usize, motion:Vector2) -> bool{
+        let object = &self.objects[id];
+        let other = &self.objects[other_id];
+        match other.collision_type {
+            CollisionType::Bounce => true,
+            CollisionType::OneWay => {
+                // Only from above, relative to the object's gravity.
+                let down = object.down();
+                let feet = object.rect.center().dot(down) + object.rect.extent(down);
+                let top = other.rect.center().dot(down) - other.rect.extent(down);
+                motion.dot(down) > 0.0 
+                    && object.drop_through_until_frame <= self.frame 
+                    && feet <= top + CONTACT_SKIN*2.0
+            }
+            _ => false,
+        }
+    }
+
+    // The object the feet of id rest on, if any.
+    fn ground(&self, id:usize) -> Option<usize>{
+        let feet = self.objects[id].feet();
+        self.grid.query(&feet).into_iter().find(|other_id| {
+            *other_id != id 
+                && matches!(self.objects[*other_id].collision_type, CollisionType::Bounce | CollisionType::OneWay) 
+                && Rect::overlaps(&feet, &self.objects[*other_id].rect)
+        })
+    }
+
+    fn friction(&self, id:usize) -> f32{
+        match self.ground(id) {
+            Some(ground_id) => self.objects[id].material.friction * self.objects[ground_id].material.friction,
+            None => 1.0,
+        }
+    }
+
+    fn restitution(&self, id:usize, other_id:usize) -> f32{
+        self.objects[id].material.restitution.max(self.objects[other_id].material.restitution)
+    }
+
+    // Moves the velocity across the down axis, horizontal with normal gravity, towards target as fast as
+    // the friction with the ground allows. Airborne objects are fully controllable, like before materials existed.
+    fn apply_friction(&mut self, id:usize, target:f32){
+        let friction = if self.objects[id].grounded { self.friction(id) } else { 1.0 };
+        let object = &mut self.objects[id];
+        let down = object.down();
+        let across = Vector2 { x: down.y.abs(), y: down.x.abs() };
+        let speed = object.velocity.dot(across);
+        object.velocity = object.velocity.add(across.mul((target / friction.max(1.0) - speed) * friction.min(1.0)));
+    }
+
+    fn ladder_at(&self, id:usize) -> Option<usize>{
+        self.overlaps(id).into_iter().find(|other_id| matches!(self.objects[*other_id].collision_type, CollisionType::Ladder))
+    }
+
+    // Pressing W or S on a ladder grabs it. While climbing gravity is ignored, the object is pulled to the
+    // center of the ladder and W with A or D jumps off. Returns whether the object is still climbing.
+    fn climb(&mut self, id:usize) -> bool{
+        let Some(ladder_id) = self.ladder_at(id) else {
+            self.objects[id].climbing = false;
+            return false;
+        };
+        let gravity = self.objects[id].down();
+        let up = self.input.is_pressed(&winit::keyboard::KeyCode::KeyW);
+        let down = self.input.is_pressed(&winit::keyboard::KeyCode::KeyS);
+        let sideways = self.input.is_pressed(&winit::keyboard::KeyCode::KeyA) != self.input.is_pressed(&winit::keyboard::KeyCode::KeyD);
+        if up || down {
+            self.objects[id].climbing = true;
+        }
+        if !self.objects[id].climbing {
+            return false;
+        }
+        if up && sideways {
+            // On the ground the regular jump takes over.
+            self.objects[id].climbing = false;
+            if !self.objects[id].grounded {
+                let velocity = self.objects[id].velocity;
+                self.objects[id].velocity = velocity.sub(gravity.mul(velocity.dot(gravity) + self.movement.jump_speed));
             }
+            return false;
         }
+        // Up and down the ladder are along the object's gravity, the ladder center is across it.
+        self.objects[id].velocity = Vector2 { x: 0.0, y: 0.0 };
+        let across = Vector2 { x: gravity.y.abs(), y: gravity.x.abs() };
+        let offset = self.objects[ladder_id].rect.center().sub(self.objects[id].rect.center()).dot(across);
+        self.slide(id, across.mul(offset));
+        let climb = if up == down { 0.0 } else if up { -self.movement.climb_speed } else { self.movement.climb_speed };
+        self.slide(id, gravity.mul(climb));
         true
     }
 
-    fn slide_x(&mut self, id:usize, distance:f32) -> bool{
-        self.objects[id].rect.x += distance;
-        for other_id in self.overlaps(id) {
-            self.apply_damage(id, other_id);
-            match self.objects[other_id].collision_type {
-                CollisionType::Bounce => {
-                    self.objects[id].rect.x -= distance;
-                    self.objects[id].velocity.x = 0.0;
-                    return true;
+    fn standing_on_one_way(&self, id:usize) -> bool{
+        let feet = self.objects[id].feet();
+        self.grid.query(&feet).into_iter().any(|other_id| {
+            other_id != id 
+                && matches!(self.objects[other_id].collision_type, CollisionType::OneWay) 
+                && Rect::overlaps(&feet, &self.objects[other_id].rect)
+        })
+    }
+
+    // How far along motion, as a fraction of it, the object first overlaps a polygon collider it didn't
+    // overlap before. Moves longer than half the object's smallest size are checked in steps of that size,
+    // so fast objects can't skip over thin ramps and bodies. Shorter moves aren't stepped, resolve_polygons
+    // handles whatever they overlap at the end.
+    fn polygon_time(&self, id:usize, motion:Vector2) -> f32{
+        let rect = self.objects[id].rect;
+        let step = rect.width.min(rect.height) / 2.0;
+        if step <= 0.0 || motion.magnitude() <= step {
+            return 1.0;
+        }
+        let start = self.objects[id].polygon();
+        let end = Rect { x: rect.x + motion.x, y: rect.y + motion.y, width: rect.width, height: rect.height };
+        let others:Vec<Vec<Vector2>> = self.grid.query(&Rect::union(&rect, &end)).into_iter()
+            .filter(|other_id| {
+                *other_id != id 
+                    && matches!(self.objects[*other_id].collision_type, CollisionType::Bounce)
+                    && (self.objects[id].is_polygon() || self.objects[*other_id].is_polygon())
+            })
+            .map(|other_id| self.objects[other_id].polygon())
+            .filter(|other| polygon::collide(&start, other).is_none())
+            .collect();
+        if others.is_empty() {
+            return 1.0;
+        }
+        let steps = (motion.magnitude() / step).ceil() as usize;
+        for index in 1..steps{
+            let time = index as f32 / steps as f32;
+            let offset = motion.mul(time);
+            let moved:Vec<Vector2> = start.iter().map(|point| point.add(offset)).collect();
+            if others.iter().any(|other| polygon::collide(&moved, other).is_some()) {
+                return time;
+            }
+        }
+        1.0
+    }
+
+    // Sweeps the object along motion and stops it just before the first Bounce object in the way,
+    // so fast objects can't tunnel through thin platforms. Returns the blocking hit, if any.
+    // Polygon colliders stop it just after it first overlaps them instead, for resolve_polygons to push it out.
+    fn sweep(&mut self, id:usize, motion:Vector2) -> Option<Hit>{
+        let rect = self.objects[id].rect;
+        let end = Rect { x: rect.x + motion.x, y: rect.y + motion.y, width: rect.width, height: rect.height };
+        let mut first_hit:Option<Hit> = None;
+        for other_id in self.grid.query(&Rect::union(&rect, &end)){
+            if other_id == id || !self.blocks(id, other_id, motion) || self.objects[other_id].is_polygon() {
+                continue;
+            }
+            if let Some(hit) = rect.sweep(motion, &self.objects[other_id].rect, other_id) {
+                if first_hit.as_ref().map_or(true, |first| hit.time < first.time) {
+                    first_hit = Some(hit);
                 }
-                CollisionType::PortalTo => {
-                    let location = self.objects[self.objects[other_id].target].rect.center();
-                    if self.portal_to(id, location){
-                        return false;
-                    }
+            }
+        }
+        let polygon_time = self.polygon_time(id, motion);
+        if first_hit.as_ref().is_some_and(|hit| hit.time > polygon_time) {
+            first_hit = None;
+        }
+        let time = match &first_hit {
+            Some(hit) => (hit.time - CONTACT_SKIN / motion.magnitude()).max(0.0),
+            None => polygon_time,
+        };
+        self.objects[id].rect.x += motion.x * time;
+        self.objects[id].rect.y += motion.y * time;
+        self.moved(id);
+        first_hit
+    }
+
+    // Pushes the object out of the polygon colliders it overlaps after a move. Walkable slopes that touch
+    // the lower half of the object push it straight up, so walking into a ramp climbs it and landing on one
+    // doesn't slide down it. Returns whether a contact opposed the motion, which is how walls and ground
+    // are reported for rects too, while walkable slopes don't block horizontal motion.
+    fn resolve_polygons(&mut self, id:usize, motion:Vector2) -> bool{
+        let (_, max_slope_cos) = sin_cos(self.physics.max_slope_angle.to_radians());
+        let direction = motion.normalize();
+        let down = self.objects[id].down();
+        let mut blocked = false;
+        for other_id in self.grid.query(&self.objects[id].rect){
+            if other_id == id || !matches!(self.objects[other_id].collision_type, CollisionType::Bounce) {
+                continue;
+            }
+            if !self.objects[id].is_polygon() && !self.objects[other_id].is_polygon() {
+                continue;
+            }
+            let Some(manifold) = polygon::collide(&self.objects[id].polygon(), &self.objects[other_id].polygon()) else {
+                continue;
+            };
+            let center = self.objects[id].rect.center();
+            let up = -manifold.normal.dot(down);
+            let walkable = up >= max_slope_cos;
+            let at_feet = manifold.contacts.iter().all(|contact| contact.sub(center).dot(down) >= 0.0);
+            let restitution = self.restitution(id, other_id);
+            let object = &mut self.objects[id];
+            if walkable && at_feet {
+                let lift = down.mul((manifold.depth + CONTACT_SKIN) / up);
+                object.rect.x -= lift.x;
+                object.rect.y -= lift.y;
+                let fall = object.velocity.dot(down);
+                if fall > 0.0 {
+                    object.velocity = object.velocity.add(down.mul(bounce(fall, restitution) - fall));
                 }
-                _ => {},
+            }
+            else{
+                // Slopes too steep to stand on push out sideways like walls, pushing out along their normal
+                // would lift whatever walks into them a little every frame until it is up the slope.
+                let side = manifold.normal.add(down.mul(up));
+                let (normal, depth) = if up > 0.0 && side.magnitude() > 0.0 {
+                    (side.normalize(), manifold.depth / side.magnitude())
+                } else {
+                    (manifold.normal, manifold.depth)
+                };
+                let push = normal.mul(depth + CONTACT_SKIN);
+                object.rect.x += push.x;
+                object.rect.y += push.y;
+                let into = object.velocity.dot(normal);
+                if into < 0.0 {
+                    let rebound = bounce(into, restitution);
+                    object.velocity = object.velocity.add(normal.mul(rebound - into));
+                }
+            }
+            self.moved(id);
+            self.apply_damage(id, other_id);
+            if manifold.normal.dot(direction) <= -max_slope_cos {
+                blocked = true;
             }
         }
-        false
+        blocked
     }
 
-    fn slide_y(&mut self, id:usize, distance:f32) -> bool{
-        self.objects[id].rect.y += distance;
+    // Keeps grounded objects walking down a slope on it instead of stepping off into the air every frame.
+    fn snap_to_ground(&mut self, id:usize, distance:f32){
+        let rect = self.objects[id].rect;
+        let (sin, cos) = sin_cos(self.physics.max_slope_angle.to_radians());
+        let down = self.objects[id].down().mul(distance * sin / cos + CONTACT_SKIN*2.0);
+        let hit = self.sweep(id, down).is_some();
+        if !hit && !self.resolve_polygons(id, down) {
+            self.objects[id].rect = rect;
+            self.moved(id);
+        }
+    }
+
+    fn slide(&mut self, id:usize, motion:Vector2) -> bool{
+        if motion.x == 0.0 && motion.y == 0.0 {
+            return false;
+        }
+        let hit = self.sweep(id, motion);
+        if let Some(hit) = &hit {
+            self.apply_damage(id, hit.other_id);
+            let restitution = self.restitution(id, hit.other_id);
+            let velocity = &mut self.objects[id].velocity;
+            if hit.normal.x != 0.0 {
+                velocity.x = bounce(velocity.x, restitution);
+            }
+            if hit.normal.y != 0.0 {
+                velocity.y = bounce(velocity.y, restitution);
+            }
+        }
+        let blocked = hit.is_some() | self.resolve_polygons(id, motion);
+        let down = self.objects[id].down();
+        if motion.dot(down) == 0.0 && !blocked && self.objects[id].grounded {
+            self.snap_to_ground(id, motion.dot(Vector2 { x: down.y, y: down.x }).abs());
+        }
         for other_id in self.overlaps(id) {
             self.apply_damage(id, other_id);
-            match self.objects[other_id].collision_type {
-                CollisionType::Bounce => {
-                    self.objects[id].rect.y -= distance;
-                    self.objects[id].velocity.y = 0.0;
-                    return true;
-                }
-                CollisionType::PortalTo => {
-                    let location = self.objects[self.objects[other_id].target].rect.center();
-                    if self.portal_to(id, location) {
-                        return false;
-                    }
-                }
-                _ => {},
+            if self.objects[other_id].portal.is_some() && self.enter_portal(id, other_id) {
+                return false;
             }
         }
-        false
+        blocked
     }
 
-    pub fn update(&mut self, mesh:&mut crate::mesh::Mesh, queue:&wgpu::Queue){
-        if self.drag.dragging {
-            let abs_rect = Self::abs_rect(
-                self.drag.x - self.cam.x, 
-                self.drag.y - self.cam.y, 
-                self.drag.x2 - self.drag.x,
-                self.drag.y2 - self.drag.y);
-            mesh.add_rect(abs_rect.0, abs_rect.1, abs_rect.2, abs_rect.3, 0.0, 0.0, 1.0);
+    fn slide_x(&mut self, id:usize, distance:f32) -> bool{
+        self.slide(id, Vector2 { x: distance, y: 0.0 })
+    }
+
+    fn slide_y(&mut self, id:usize, distance:f32) -> bool{
+        self.slide(id, Vector2 { x: 0.0, y: distance })
+    }
+
+    fn rebuild_grid(&mut self){
+        self.grid.clear();
+        for (id, object) in self.objects.iter().enumerate(){
+            self.grid.insert(id, &object.rect);
         }
+    }
+
+    // Has to be called whenever an object's rect changes so the grid stays in sync.
+    fn moved(&mut self, id:usize){
+        self.grid.update(id, &self.objects[id].rect);
+    }
+
+    fn add_object(&mut self, object:Object){
+        if object.is_static() {
+            self.static_version += 1;
+        }
+        self.grid.insert(self.objects.len(), &object.rect);
+        self.objects.push(object);
+    }
+
+    #[allow(clippy::single_match)]
+    pub fn update(&mut self){
         match self.mode {
             Mode::Play => {
+                self.step_input();
                 for i in 0..self.objects.len(){
-                    self.objects[i].velocity.y += self.objects[i].gravity;
-                    self.slide_x(i, self.objects[i].velocity.x);
-                    let vy = self.objects[i].velocity.y;
-                    let grounded = self.slide_y(i, vy) && vy >= 0.0;
+                    if self.objects[i].body.is_some() {
+                        self.step_body(i);
+                        continue;
+                    }
+                    self.apply_gravity(i);
+                    self.apply_liquid(i);
+                    let air_drag = self.objects[i].material.air_drag;
+                    self.objects[i].velocity = self.objects[i].velocity.mul(1.0 - air_drag);
+                    let carry = self.apply_forces(i);
+                    self.clamp_fall(i);
+                    let grounded = self.fall(i, self.objects[i].velocity.add(carry));
+                    self.objects[i].grounded = grounded;
                     match self.objects[i].controller {
                         Controller::Player => {
-                            if self.input.is_pressed(&winit::keyboard::KeyCode::KeyW){
-                                if grounded { 
-                                    self.objects[i].velocity.y -= self.jump_force;
-                                }
-                            }
-                            if !(self.input.is_pressed(&winit::keyboard::KeyCode::KeyA) && self.input.is_pressed(&winit::keyboard::KeyCode::KeyD)){
-                                if self.input.is_pressed(&winit::keyboard::KeyCode::KeyA){
-                                    self.slide_x(i, -self.speed);
-                                }
-                                if self.input.is_pressed(&winit::keyboard::KeyCode::KeyD){
-                                    self.slide_x(i, self.speed);
-                                }
+                            // Knockback keeps its momentum while stunned, friction and input take over after.
+                            let stunned = self.objects[i].hitstun_until_frame > self.frame;
+                            if !stunned && !self.climb(i) {
+                                self.move_player(i, grounded);
                             }
                             
-                            if self.input.is_pressed(&winit::keyboard::KeyCode::Space) {
-                                if self.objects[i].enable_firing_at_frame <= self.frame{
-                                    self.objects[i].enable_firing_at_frame = self.frame+30;
-                                    let center = self.objects[i].rect.center();
-                                    let mousepos = self.get_relative_mouse_position();
-                                    let offset_to_mouse = Vector2 { x:mousepos.x - center.x, y:mousepos.y - center.y };
-                                    let direction = offset_to_mouse.normalize().scale(self.objects[i].rect.width, self.objects[i].rect.height);
-                                    self.objects.push(Object { 
-                                        controller: Controller::FollowTarget, 
-                                        rect: self.objects[i].rect.clone(), 
-                                        color: Color { r: 1.0, g: 1.0, b: 0.2 }, 
-                                        velocity: Vector2 { x: 0.0, y: 0.0 }, 
-                                        gravity: 0.0, 
-                                        direction, 
-                                        collision_type: CollisionType::None, 
-                                        destroying: true, 
-                                        destroy_at_frame: self.frame+20, 
-                                        enable_firing_at_frame: 0,
-                                        target: i,
-                                        faction: self.objects[i].faction,
-                                        health: 0,
-                                        max_health: 0,
-                                        damage: 5,
-                                        disable_damage_bar_at_frame:0,
-                                     });
-                                }
+                            if !stunned && self.input.is_pressed(&winit::keyboard::KeyCode::Space) && self.objects[i].enable_firing_at_frame <= self.frame {
+                                self.objects[i].enable_firing_at_frame = self.frame+30;
+                                let center = self.objects[i].rect.center();
+                                let mousepos = self.get_relative_mouse_position();
+                                let offset_to_mouse = Vector2 { x:mousepos.x - center.x, y:mousepos.y - center.y };
+                                let direction = offset_to_mouse.normalize().scale(self.objects[i].rect.width, self.objects[i].rect.height);
+                                self.add_object(Object {
+                                    controller: Controller::FollowTarget,
+                                    direction,
+                                    collision_type: CollisionType::None,
+                                    destroying: true,
+                                    destroy_at_frame: self.frame+20,
+                                    target: i,
+                                    faction: self.objects[i].faction,
+                                    damage: 5,
+                                    knockback: 8.0,
+                                    ..Object::new(self.objects[i].rect, Color { r: 1.0, g: 1.0, b: 0.2 })
+                                });
                             }
                             let player_position = self.objects[i].rect.center();
                             self.cam.x = player_position.x - self.screen.x/2.0;
                             self.cam.y = player_position.y - self.screen.y/2.0;
                         }
-                        Controller::AI => {
-                            if self.slide_x(i, self.speed * self.objects[i].direction.x) {
+                        Controller::Platform => {
+                            self.update_platform(i);
+                        }
+                        Controller::None if grounded => {
+                            self.apply_friction(i, 0.0);
+                        }
+                        Controller::AI if self.objects[i].hitstun_until_frame <= self.frame => {
+                            if grounded {
+                                self.apply_friction(i, 0.0);
+                            }
+                            if self.slide_x(i, self.movement.run_speed * self.objects[i].direction.x) {
                                 self.objects[i].direction.x *= -1.0;
                             }
                             if grounded {
-                                self.objects[i].velocity.y -= self.jump_force;
+                                let jump = self.objects[i].down().mul(self.movement.jump_speed);
+                                self.objects[i].velocity = self.objects[i].velocity.sub(jump);
                             }
                         }
                         _ => {}
@@ -530,11 +1425,15 @@ impl VectorGraphics {
                                 let new_center = self.objects[self.objects[i].target].rect.center();
                                 self.objects[i].rect.x = new_center.x - self.objects[i].rect.width / 2.0 + self.objects[i].direction.x;
                                 self.objects[i].rect.y = new_center.y - self.objects[i].rect.height / 2.0 + self.objects[i].direction.y;
+                                self.moved(i);
                             }
                             _ => {}
                         }
                     }
                 }
+                self.solve_joints();
+                self.update_triggers();
+                self.process_trigger_events();
             }
             Mode::Edit => {
                 if self.input.is_pressed(&winit::keyboard::KeyCode::ArrowLeft) {
@@ -552,46 +1451,498 @@ impl VectorGraphics {
             }
             
         }
-        for object in &self.objects{
-            mesh.add_rect(
-                object.rect.x - self.cam.x, 
-                object.rect.y - self.cam.y, 
-                object.rect.width, 
-                object.rect.height, 
-                object.color.r, 
-                object.color.g, 
-                object.color.b);
-        }
-        for object in &self.objects {
-            if object.disable_damage_bar_at_frame > self.frame {
-                mesh.add_rect(
-                    object.rect.x - self.cam.x, 
-                    object.rect.y - self.cam.y - object.rect.height/2.0 - 20.0, 
-                    object.rect.width * (object.health as f32 / object.max_health as f32), 
-                    10.0, 
-                    0.0, 
-                    1.0, 
-                    0.0)
-            }
-        }
-
         {
             let mut i = 0;
+            let mut removed = false;
             while i < self.objects.len(){
                 if self.objects[i].destroying && self.objects[i].destroy_at_frame <= self.frame {
+                    if self.objects[i].is_static() {
+                        self.static_version += 1;
+                    }
                     self.objects.remove(i);
+                    removed = true;
                     for ii in 0..self.objects.len(){
                         if self.objects[ii].target > i{
                             self.objects[ii].target -= 1;
                         }
+                        match &mut self.objects[ii].trigger {
+                            Some(trigger) => trigger.removed(i),
+                            _ => {}
+                        }
+                        self.objects[ii].joints.retain_mut(|joint| joint.removed(i));
                     }
+                    self.unread_trigger_events.retain_mut(|event| event.removed(i));
                 }
                 i+=1;
             }
+            // Removing shifts the ids of everything after it, so the grid is rebuilt from scratch.
+            if removed {
+                self.last_portal = None;
+                self.last_platform = None;
+                self.last_trigger = None;
+                self.joint_start = None;
+                self.rebuild_grid();
+            }
         }
-        
-
-        mesh.update_queue(queue);
         self.frame+=1;
     }
-}
\ No newline at end of file
+
+    // The rect of the world that is visible on screen, used as the camera of the main view.
+    pub fn view(&self) -> (f32,f32,f32,f32){
+        (self.cam.x, self.cam.y, self.screen.x, self.screen.y)
+    }
+
+    fn view_rect(&self) -> Rect{
+        Rect { x: self.cam.x, y: self.cam.y, width: self.screen.x, height: self.screen.y }
+    }
+
+    // Only objects overlapping the view, as found by the grid, are drawn.
+    pub fn draw(&self, geometry:&mut Geometry){
+        let view = self.view_rect();
+        let (x, y, width, height) = geometry.static_bounds();
+        let static_bounds = Rect { x, y, width, height };
+        if geometry.static_version() != Some(self.static_version) || !static_bounds.contains_rect(&view) {
+            let bounds = view.expand(STATIC_CULL_MARGIN);
+            geometry.begin_static();
+            for id in self.grid.query(&bounds){
+                let object = &self.objects[id];
+                if object.is_static() && !object.uses_sdf() && Rect::overlaps(&object.rect, &bounds) {
+                    object.draw(geometry);
+                }
+            }
+            geometry.end_static(self.static_version, (bounds.x, bounds.y, bounds.width, bounds.height));
+        }
+        if self.drag.dragging {
+            let abs_rect = Self::abs_rect(
+                self.drag.x, 
+                self.drag.y, 
+                self.drag.x2 - self.drag.x,
+                self.drag.y2 - self.drag.y);
+            geometry.add_rect(abs_rect.0, abs_rect.1, abs_rect.2, abs_rect.3, 0.0, 0.0, 1.0);
+        }
+        // Everything the static batch leaves out: dynamic objects and the static sdf objects.
+        for id in self.grid.query(&view){
+            let object = &self.objects[id];
+            let blinking = object.invulnerable_until_frame > self.frame && (self.frame / 4) % 2 == 0;
+            let hidden = (object.trigger.is_some() && matches!(self.mode, Mode::Play)) || blinking;
+            if (!object.is_static() || object.uses_sdf()) && !hidden && Rect::overlaps(&object.rect, &view) {
+                object.draw(geometry);
+            }
+        }
+        for object in &self.objects {
+            if object.disable_damage_bar_at_frame > self.frame {
+                let bar = Rect {
+                    x: object.rect.x, 
+                    y: object.rect.y - object.rect.height/2.0 - 20.0, 
+                    width: object.rect.width * (object.health as f32 / object.max_health as f32), 
+                    height: 10.0,
+                };
+                if Rect::overlaps(&bar, &view) {
+                    geometry.add_rect(bar.x, bar.y, bar.width, bar.height, 0.0, 1.0, 0.0);
+                }
+            }
+        }
+        self.draw_joints(geometry);
+        if matches!(self.mode, Mode::Edit) {
+            self.draw_paths(geometry);
+            self.draw_portals(geometry);
+            self.draw_forces(geometry);
+        }
+        self.draw_minimap_frame(geometry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::software_rasterizer::Image;
+
+    fn level(objects:Vec<Object>) -> VectorGraphics{
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.resize(800.0, 600.0);
+        for object in objects{
+            vector_graphics.add_object(object);
+        }
+        vector_graphics
+    }
+
+    fn player(rect:Rect) -> Object{
+        Object {
+            faction: FACTION_PLAYER,
+            controller: Controller::Player,
+            gravity: 0.3,
+            ..Object::new(rect, Color { r: 1.0, g: 0.5, b: 0.0 })
+        }
+    }
+
+    #[test]
+    fn player_is_drawn_in_a_dense_level(){
+        let mut objects = Vec::new();
+        for x in 0..40{
+            for y in 0..10{
+                objects.push(Object::new(Rect { x: x as f32 * 20.0, y: y as f32 * 20.0, width: 20.0, height: 20.0 }, Color { r: 0.025, g: 0.025, b: 0.025 }));
+            }
+        }
+        objects.push(player(Rect { x: 380.0, y: 300.0, width: 40.0, height: 40.0 }));
+        let vector_graphics = level(objects);
+        let mut geometry = Geometry::new(2000);
+        vector_graphics.draw(&mut geometry);
+        assert!(geometry.vertices().iter().any(|vertex| vertex.color == [1.0, 0.5, 0.0]));
+    }
+
+    #[test]
+    fn level_renders_through_the_camera_and_minimap(){
+        let mut vector_graphics = level(vec![
+            Object::new(Rect { x: 100.0, y: 400.0, width: 200.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 1.0 }),
+            player(Rect { x: 500.0, y: 300.0, width: 40.0, height: 40.0 }),
+            Object::new(Rect { x: 3000.0, y: 400.0, width: 200.0, height: 50.0 }, Color { r: 0.0, g: 1.0, b: 0.0 }),
+        ]);
+        vector_graphics.cam = Vector2 { x: 100.0, y: 0.0 };
+        let mut geometry = Geometry::new(2000);
+        let mut minimap_geometry = Geometry::new(2000);
+        vector_graphics.draw(&mut geometry);
+        let minimap = vector_graphics.draw_minimap(&mut minimap_geometry).unwrap();
+        // Far outside the view, so culled from the static batch.
+        assert!(!geometry.vertices().iter().any(|vertex| vertex.color == [0.0, 1.0, 0.0]));
+        let mut image = Image::new(800, 600);
+        image.rasterize(&geometry, vector_graphics.view(), (0.0, 0.0, 800.0, 600.0));
+        image.rasterize(&minimap_geometry, minimap.view, minimap.viewport);
+        let pixel = |x:usize, y:usize| image.pixels[y*image.width + x];
+        let clear = Image::new(1, 1).pixels[0];
+
+        // The static terrain and the player are shifted left by the camera.
+        assert_eq!(pixel(5, 420), [0, 0, 255, 255]);
+        assert_eq!(pixel(195, 445), [0, 0, 255, 255]);
+        assert_eq!(pixel(205, 420), clear);
+        assert_eq!(pixel(420, 320), [255, 188, 0, 255]);
+        assert_eq!(pixel(500, 320), clear);
+
+        // The minimap background in its corner, and the player as a dot where it is in the level.
+        let (viewport_x, viewport_y, viewport_width, viewport_height) = minimap.viewport;
+        let (view_x, view_y, view_width, view_height) = minimap.view;
+        assert_eq!(pixel(viewport_x as usize + 2, viewport_y as usize + 2), [89, 89, 89, 255]);
+        let dot_x = viewport_x + (520.0 - view_x) / view_width * viewport_width;
+        let dot_y = viewport_y + (320.0 - view_y) / view_height * viewport_height;
+        assert_eq!(pixel(dot_x as usize, dot_y as usize), [124, 203, 255, 255]);
+    }
+
+    #[test]
+    fn physics_config_loads_over_the_defaults(){
+        let path = std::env::temp_dir().join("vector_graphics_physics_test.json");
+        std::fs::write(&path, r#"{ "terminal_velocity": 5.0, "hitstun_frames": 3 }"#).unwrap();
+        let mut vector_graphics = level(vec![
+            Object { gravity: 1.0, ..Object::new(Rect { x: 0.0, y: 0.0, width: 10.0, height: 10.0 }, Color { r: 1.0, g: 1.0, b: 1.0 }) },
+        ]);
+        vector_graphics.load_physics(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(vector_graphics.physics.hitstun_frames, 3);
+        assert_eq!(vector_graphics.physics.max_slope_angle, PhysicsConfig::default().max_slope_angle);
+        vector_graphics.mode = Mode::Play;
+        for _ in 0..30{
+            vector_graphics.update();
+        }
+        assert_eq!(vector_graphics.objects[0].velocity.y, 5.0);
+    }
+
+    #[test]
+    fn fast_objects_land_on_thin_polygons(){
+        for thickness in 2..=12{
+            let thickness = thickness as f32;
+            let platform = Shape::Polygon(vec![
+                Vector2 { x: 0.0, y: 0.0 },
+                Vector2 { x: 200.0, y: 0.0 },
+                Vector2 { x: 200.0, y: thickness },
+                Vector2 { x: 0.0, y: thickness },
+            ]);
+            let mut vector_graphics = level(vec![
+                Object { shape: platform, ..Object::new(Rect { x: -100.0, y: 1000.0, width: 200.0, height: thickness }, Color { r: 0.0, g: 0.0, b: 0.0 }) },
+                Object { gravity: 1.0, ..Object::new(Rect { x: 0.0, y: 0.0, width: 8.0, height: 8.0 }, Color { r: 1.0, g: 1.0, b: 1.0 }) },
+            ]);
+            vector_graphics.mode = Mode::Play;
+            for _ in 0..200{
+                vector_graphics.update();
+            }
+            let rect = vector_graphics.objects[1].rect;
+            assert!((rect.y + rect.height - 1000.0).abs() < 0.1, "fell to {} through a {}px polygon", rect.y, thickness);
+        }
+    }
+
+    #[test]
+    fn fast_objects_land_on_thin_rects(){
+        let mut vector_graphics = level(vec![
+            Object::new(Rect { x: -100.0, y: 1000.0, width: 200.0, height: 2.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }),
+            Object { gravity: 1.0, ..Object::new(Rect { x: 0.0, y: 0.0, width: 8.0, height: 8.0 }, Color { r: 1.0, g: 1.0, b: 1.0 }) },
+        ]);
+        // Falling many times the platform's thickness every frame.
+        vector_graphics.physics.terminal_velocity = 60.0;
+        vector_graphics.mode = Mode::Play;
+        let mut fastest:f32 = 0.0;
+        for _ in 0..200{
+            fastest = fastest.max(vector_graphics.objects[1].velocity.y);
+            vector_graphics.update();
+        }
+        let rect = vector_graphics.objects[1].rect;
+        assert!(fastest > 30.0);
+        assert!((rect.y + rect.height - 1000.0).abs() < 0.1, "fell to {}", rect.y);
+    }
+
+    #[test]
+    fn sweeps_find_the_time_and_side_of_impact(){
+        let wall = Rect { x: 100.0, y: 0.0, width: 10.0, height: 100.0 };
+        let moving = Rect { x: 0.0, y: 40.0, width: 20.0, height: 20.0 };
+        let hit = moving.sweep(Vector2 { x: 160.0, y: 0.0 }, &wall, 7).unwrap();
+        assert!((hit.time - 0.5).abs() < 0.001);
+        assert_eq!((hit.normal.x, hit.normal.y, hit.other_id), (-1.0, 0.0, 7));
+
+        // Diagonally onto the top, the axis entered last decides the normal.
+        let hit = Rect { x: 95.0, y: -50.0, width: 20.0, height: 20.0 }.sweep(Vector2 { x: 10.0, y: 60.0 }, &wall, 7).unwrap();
+        assert!((hit.time - 0.5).abs() < 0.001);
+        assert_eq!((hit.normal.x, hit.normal.y), (0.0, -1.0));
+
+        // Falling short, moving away, and passing beside it miss.
+        assert!(moving.sweep(Vector2 { x: 70.0, y: 0.0 }, &wall, 7).is_none());
+        assert!(moving.sweep(Vector2 { x: -160.0, y: 0.0 }, &wall, 7).is_none());
+        assert!(moving.sweep(Vector2 { x: 0.0, y: 100.0 }, &wall, 7).is_none());
+
+        // Already overlapping only stops motion going deeper.
+        let stuck = Rect { x: 92.0, y: 40.0, width: 20.0, height: 20.0 };
+        let hit = stuck.sweep(Vector2 { x: 5.0, y: 0.0 }, &wall, 7).unwrap();
+        assert_eq!((hit.time, hit.normal.x), (0.0, -1.0));
+        assert!(stuck.sweep(Vector2 { x: -5.0, y: 0.0 }, &wall, 7).is_none());
+    }
+
+    // Flat ground with a ramp rising rise over 200 to a plateau, and the player on the ground left of it.
+    fn ramp(rise:f32) -> VectorGraphics{
+        let slope = Shape::Polygon(vec![
+            Vector2 { x: 0.0, y: rise },
+            Vector2 { x: 200.0, y: 0.0 },
+            Vector2 { x: 200.0, y: rise },
+        ]);
+        let mut vector_graphics = level(vec![
+            Object::new(Rect { x: 0.0, y: 400.0, width: 1000.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }),
+            Object { shape: slope, ..Object::new(Rect { x: 300.0, y: 400.0 - rise, width: 200.0, height: rise }, Color { r: 0.0, g: 0.0, b: 0.0 }) },
+            Object::new(Rect { x: 500.0, y: 400.0 - rise, width: 500.0, height: rise }, Color { r: 0.0, g: 0.0, b: 0.0 }),
+            player(Rect { x: 100.0, y: 360.0 - CONTACT_SKIN, width: 40.0, height: 40.0 }),
+        ]);
+        vector_graphics.mode = Mode::Play;
+        vector_graphics.update();
+        vector_graphics
+    }
+
+    #[test]
+    fn players_walk_up_and_down_ramps_without_leaving_them(){
+        let mut vector_graphics = ramp(100.0);
+        let bottom = |vector_graphics:&VectorGraphics| vector_graphics.objects[3].rect.y + vector_graphics.objects[3].rect.height;
+        // Up the ramp the feet only ever rise and down it they only ever sink, without a frame in the air.
+        for (key, rising) in [(winit::keyboard::KeyCode::KeyD, true), (winit::keyboard::KeyCode::KeyA, false)] {
+            vector_graphics.keydown(key);
+            for _ in 0..120{
+                let before = bottom(&vector_graphics);
+                vector_graphics.update();
+                let after = bottom(&vector_graphics);
+                assert!(vector_graphics.objects[3].grounded);
+                assert!(if rising { after <= before + 0.001 } else { after >= before - 0.001 }, "{} then {}", before, after);
+            }
+            vector_graphics.keyup(key);
+            let expected = if rising { 300.0 } else { 400.0 };
+            assert!((bottom(&vector_graphics) - expected).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn ramps_steeper_than_the_max_slope_angle_are_walls(){
+        let climb = |max_slope_angle:f32| {
+            let mut vector_graphics = ramp(300.0);
+            vector_graphics.physics.max_slope_angle = max_slope_angle;
+            vector_graphics.keydown(winit::keyboard::KeyCode::KeyD);
+            for _ in 0..120{
+                vector_graphics.update();
+            }
+            vector_graphics.objects[3].rect
+        };
+        // The ramp is about 56 degrees.
+        let blocked = climb(50.0);
+        assert!(blocked.x + blocked.width < 310.0 && blocked.y + blocked.height > 399.0);
+        let climbed = climb(60.0);
+        assert!((climbed.y + climbed.height - 100.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn players_drop_through_one_way_platforms(){
+        let mut vector_graphics = level(vec![
+            Object { collision_type: CollisionType::OneWay, ..Object::new(Rect { x: 0.0, y: 200.0, width: 200.0, height: 10.0 }, Color { r: 0.5, g: 0.5, b: 0.5 }) },
+            Object::new(Rect { x: 0.0, y: 400.0, width: 200.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }),
+            player(Rect { x: 80.0, y: 150.0, width: 40.0, height: 40.0 }),
+        ]);
+        vector_graphics.mode = Mode::Play;
+        let bottom = |vector_graphics:&VectorGraphics| vector_graphics.objects[2].rect.y + vector_graphics.objects[2].rect.height;
+        for _ in 0..30{
+            vector_graphics.update();
+        }
+        assert!((bottom(&vector_graphics) - 200.0).abs() < 0.1);
+        assert!(vector_graphics.objects[2].grounded);
+
+        vector_graphics.keydown(winit::keyboard::KeyCode::KeyS);
+        vector_graphics.keydown(winit::keyboard::KeyCode::KeyW);
+        vector_graphics.update();
+        vector_graphics.keyup(winit::keyboard::KeyCode::KeyS);
+        vector_graphics.keyup(winit::keyboard::KeyCode::KeyW);
+        for _ in 0..60{
+            vector_graphics.update();
+        }
+        assert!((bottom(&vector_graphics) - 400.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn one_way_platforms_let_jumps_through_from_below(){
+        let mut vector_graphics = level(vec![
+            Object { collision_type: CollisionType::OneWay, ..Object::new(Rect { x: 0.0, y: 300.0, width: 200.0, height: 10.0 }, Color { r: 0.5, g: 0.5, b: 0.5 }) },
+            Object::new(Rect { x: 0.0, y: 400.0, width: 200.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }),
+            player(Rect { x: 80.0, y: 359.0, width: 40.0, height: 40.0 }),
+        ]);
+        vector_graphics.mode = Mode::Play;
+        vector_graphics.update();
+        vector_graphics.keydown(winit::keyboard::KeyCode::KeyW);
+        for _ in 0..90{
+            vector_graphics.update();
+        }
+        let rect = vector_graphics.objects[2].rect;
+        assert!((rect.y + rect.height - 300.0).abs() < 0.1);
+    }
+
+    // The speed a box dropped onto the ground falls with and bounces back up with, the highest restitution of the two counts.
+    fn drop_box(ground:Material, material:Material) -> (f32, f32){
+        let mut vector_graphics = level(vec![
+            Object { material: ground, ..Object::new(Rect { x: 0.0, y: 300.0, width: 200.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }) },
+            Object { gravity: 0.3, material, ..Object::new(Rect { x: 80.0, y: 0.0, width: 40.0, height: 40.0 }, Color { r: 1.0, g: 1.0, b: 1.0 }) },
+        ]);
+        vector_graphics.mode = Mode::Play;
+        for _ in 0..300{
+            let fall = vector_graphics.objects[1].velocity.y + vector_graphics.gravity.y * 0.3;
+            vector_graphics.update();
+            let rebound = vector_graphics.objects[1].velocity.y;
+            if rebound <= 0.0 && fall > 0.0 {
+                return (fall, rebound);
+            }
+        }
+        panic!("the box never landed");
+    }
+
+    #[test]
+    fn restitution_bounces_back_part_of_the_impact(){
+        let (fall, rebound) = drop_box(MATERIAL_TRAMPOLINE, Material::default());
+        assert!(fall > 10.0 && (rebound + fall).abs() < 0.001);
+        let bouncy = Material { restitution: 0.5, ..Material::default() };
+        let (fall, rebound) = drop_box(Material::default(), bouncy);
+        assert!((rebound + fall * 0.5).abs() < 0.001);
+        let (_, rebound) = drop_box(Material::default(), Material::default());
+        assert_eq!(rebound, 0.0);
+    }
+
+    // A player with health standing on the ground, and two spikes that can hurt it on either side.
+    fn hurtable_player() -> VectorGraphics{
+        let spike = |x:f32| Object {
+            faction: FACTION_ENEMY,
+            collision_type: CollisionType::None,
+            damage: 10,
+            knockback: 8.0,
+            ..Object::new(Rect { x, y: 360.0, width: 20.0, height: 40.0 }, Color { r: 1.0, g: 0.0, b: 0.0 })
+        };
+        let mut vector_graphics = level(vec![
+            Object::new(Rect { x: 0.0, y: 400.0, width: 800.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }),
+            Object { health: 100, max_health: 100, ..player(Rect { x: 380.0, y: 360.0 - CONTACT_SKIN, width: 40.0, height: 40.0 }) },
+            spike(300.0),
+            spike(480.0),
+        ]);
+        vector_graphics.mode = Mode::Play;
+        vector_graphics.update();
+        vector_graphics
+    }
+
+    #[test]
+    fn knockback_pushes_away_from_the_source_and_up(){
+        for (spike, side) in [(2, 1.0), (3, -1.0)] {
+            let mut vector_graphics = hurtable_player();
+            vector_graphics.apply_damage(1, spike);
+            let velocity = vector_graphics.objects[1].velocity;
+            assert!(velocity.x * side > 0.0 && velocity.y < 0.0);
+            assert!((velocity.magnitude() - 8.0).abs() < 0.001);
+            assert_eq!(vector_graphics.objects[1].health, 90);
+        }
+    }
+
+    #[test]
+    fn hitstun_ignores_input_until_it_is_over(){
+        let mut vector_graphics = hurtable_player();
+        vector_graphics.apply_damage(1, 3);
+        let objects = vector_graphics.objects.len();
+        vector_graphics.keydown(winit::keyboard::KeyCode::KeyD);
+        vector_graphics.keydown(winit::keyboard::KeyCode::Space);
+        for _ in 0..vector_graphics.physics.hitstun_frames{
+            vector_graphics.update();
+            // Still flying left and not shooting, even with right and fire held.
+            assert!(vector_graphics.objects[1].velocity.x < 0.0);
+            assert_eq!(vector_graphics.objects.len(), objects);
+        }
+        let mut fired = false;
+        for _ in 0..30{
+            vector_graphics.update();
+            fired |= vector_graphics.objects.len() > objects;
+        }
+        assert!(vector_graphics.objects[1].velocity.x > 0.0 && fired);
+    }
+
+    #[test]
+    fn invulnerable_objects_take_no_damage(){
+        let mut vector_graphics = hurtable_player();
+        vector_graphics.apply_damage(1, 2);
+        let velocity = vector_graphics.objects[1].velocity;
+        vector_graphics.frame += vector_graphics.physics.invulnerability_frames - 1;
+        vector_graphics.apply_damage(1, 3);
+        // The second spike neither hurts nor pushes, and keeps its damage for later.
+        assert_eq!(vector_graphics.objects[1].health, 90);
+        assert_eq!((vector_graphics.objects[1].velocity.x, vector_graphics.objects[1].velocity.y), (velocity.x, velocity.y));
+        assert_eq!(vector_graphics.objects[3].damage, 10);
+        vector_graphics.frame += 1;
+        vector_graphics.apply_damage(1, 3);
+        assert_eq!(vector_graphics.objects[1].health, 80);
+    }
+
+    #[test]
+    fn climbing_snaps_to_the_ladder_and_ignores_gravity(){
+        let mut vector_graphics = level(vec![
+            Object::new(Rect { x: 0.0, y: 400.0, width: 400.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }),
+            Object { collision_type: CollisionType::Ladder, ..Object::new(Rect { x: 100.0, y: 100.0, width: 40.0, height: 300.0 }, Color { r: 0.5, g: 0.3, b: 0.1 }) },
+            player(Rect { x: 110.0, y: 355.0, width: 40.0, height: 40.0 }),
+        ]);
+        vector_graphics.mode = Mode::Play;
+        for _ in 0..10{
+            vector_graphics.update();
+        }
+        assert!(!vector_graphics.objects[2].climbing);
+        let y = vector_graphics.objects[2].rect.y;
+        vector_graphics.keydown(winit::keyboard::KeyCode::KeyW);
+        for _ in 0..20{
+            vector_graphics.update();
+        }
+        vector_graphics.keyup(winit::keyboard::KeyCode::KeyW);
+        assert!(vector_graphics.objects[2].climbing);
+        assert!((vector_graphics.objects[2].rect.center().x - 120.0).abs() < 0.01);
+        let climbed = y - vector_graphics.objects[2].rect.y;
+        assert!((climbed - 20.0 * vector_graphics.movement.climb_speed).abs() < 0.01);
+
+        // Letting go of W hangs on the ladder.
+        let y = vector_graphics.objects[2].rect.y;
+        for _ in 0..20{
+            vector_graphics.update();
+        }
+        assert_eq!(vector_graphics.objects[2].rect.y, y);
+
+        // W with a direction jumps off.
+        vector_graphics.keydown(winit::keyboard::KeyCode::KeyW);
+        vector_graphics.keydown(winit::keyboard::KeyCode::KeyD);
+        vector_graphics.update();
+        assert!(!vector_graphics.objects[2].climbing);
+        assert!(vector_graphics.objects[2].velocity.y < 0.0);
+    }
+}
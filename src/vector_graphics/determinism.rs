@@ -0,0 +1,324 @@
+use super::*;
+use winit::keyboard::KeyCode;
+
+// The keys the simulation reads, stored as a bit mask per frame.
+const RECORDED_KEYS:[KeyCode; 6] = [KeyCode::KeyW, KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD, KeyCode::Space, KeyCode::ShiftLeft];
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub(super) struct InputFrame{
+    keys:u32,
+    mouse:Vector2,
+}
+
+// Everything needed to play a level back exactly: the level as it was when recording started, the
+// physics it was played with, the camera and screen that the mouse position depends on, and the input
+// of every frame since. Recordings from before physics was saved get the default physics.
+#[derive(Serialize, Deserialize)]
+pub(super) struct Replay{
+    level:serde_json::Value,
+    #[serde(default)]
+    physics:PhysicsConfig,
+    frame:usize,
+    cam:Vector2,
+    screen:Vector2,
+    inputs:Vec<InputFrame>,
+}
+
+// sin and cos from plain arithmetic, so they round the same on every platform, unlike the ones from
+// the system math library.
+pub(super) fn sin_cos(angle:f32) -> (f32, f32){
+    let angle = angle as f64;
+    let quadrant = (angle / std::f64::consts::FRAC_PI_2).round();
+    let x = angle - quadrant * std::f64::consts::FRAC_PI_2;
+    let x2 = x * x;
+    let sin = x * (1.0 - x2 / 6.0 * (1.0 - x2 / 20.0 * (1.0 - x2 / 42.0 * (1.0 - x2 / 72.0))));
+    let cos = 1.0 - x2 / 2.0 * (1.0 - x2 / 12.0 * (1.0 - x2 / 30.0 * (1.0 - x2 / 56.0 * (1.0 - x2 / 90.0))));
+    let (sin, cos) = match (quadrant as i64).rem_euclid(4) {
+        0 => (sin, cos),
+        1 => (cos, -sin),
+        2 => (-sin, -cos),
+        _ => (-cos, sin),
+    };
+    (sin as f32, cos as f32)
+}
+
+// FNV-1a, written out so the hash doesn't depend on the standard library's hasher.
+struct StateHasher{
+    hash:u64,
+}
+
+impl StateHasher{
+    fn bytes(&mut self, bytes:&[u8]){
+        for byte in bytes{
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn f32(&mut self, value:f32){
+        self.bytes(&value.to_bits().to_le_bytes());
+    }
+
+    fn usize(&mut self, value:usize){
+        self.bytes(&(value as u64).to_le_bytes());
+    }
+
+    // Written before every optional value, so a missing value can't hash the same as a present one.
+    fn tag(&mut self, present:bool){
+        self.bytes(&[present as u8]);
+    }
+
+    fn vector(&mut self, value:Vector2){
+        self.f32(value.x);
+        self.f32(value.y);
+    }
+
+    fn rect(&mut self, value:&Rect){
+        self.f32(value.x);
+        self.f32(value.y);
+        self.f32(value.width);
+        self.f32(value.height);
+    }
+}
+
+impl VectorGraphics {
+    // Hash of everything the simulation changes, down to the bits of every float, so two runs that
+    // should be identical can be compared frame by frame.
+    pub fn state_hash(&self) -> u64{
+        let mut hasher = StateHasher { hash: 0xcbf29ce484222325 };
+        hasher.usize(self.frame);
+        hasher.bytes(&[matches!(self.mode, Mode::Play) as u8]);
+        hasher.vector(self.cam);
+        hasher.vector(self.gravity);
+        hasher.usize(self.objects.len());
+        for object in &self.objects{
+            hasher.rect(&object.rect);
+            hasher.vector(object.velocity);
+            hasher.vector(object.direction);
+            hasher.f32(object.gravity);
+            hasher.bytes(&object.health.to_le_bytes());
+            hasher.bytes(&object.damage.to_le_bytes());
+            hasher.usize(object.target);
+            hasher.usize(object.destroy_at_frame);
+            hasher.usize(object.enable_firing_at_frame);
+            hasher.usize(object.drop_through_until_frame);
+            hasher.usize(object.swim_stroke_at_frame);
+            hasher.usize(object.hitstun_until_frame);
+            hasher.usize(object.invulnerable_until_frame);
+            hasher.usize(object.portal_cooldown_until_frame);
+            hasher.vector(object.gravity_direction);
+            hasher.tag(object.standing_size.is_some());
+            if let Some(size) = object.standing_size {
+                hasher.vector(size);
+            }
+            hasher.bytes(&[object.destroying as u8, object.grounded as u8, object.climbing as u8]);
+            let movement = &object.movement;
+            for at_frame in [movement.grounded_at_frame, movement.jump_pressed_at_frame] {
+                hasher.tag(at_frame.is_some());
+                hasher.usize(at_frame.unwrap_or(0));
+            }
+            hasher.usize(movement.dash_until_frame);
+            hasher.usize(movement.wall_jump_until_frame);
+            hasher.f32(movement.dash_direction);
+            hasher.f32(movement.facing);
+            hasher.bytes(&[movement.jump_held as u8, movement.dash_held as u8, movement.jumping as u8, movement.can_dash as u8]);
+            hasher.tag(object.path.is_some());
+            if let Some(path) = &object.path {
+                hasher.usize(path.segment);
+                hasher.f32(path.progress);
+                hasher.bytes(&[path.forward as u8]);
+            }
+            hasher.tag(object.body.is_some());
+            if let Some(body) = &object.body {
+                hasher.f32(body.angle);
+                hasher.f32(body.angular_velocity);
+            }
+            hasher.tag(object.trigger.is_some());
+            if let Some(trigger) = &object.trigger {
+                hasher.bytes(&[trigger.fired as u8]);
+                hasher.tag(trigger.target.is_some());
+                hasher.usize(trigger.target.unwrap_or(0));
+                hasher.usize(trigger.inside.len());
+                for other in &trigger.inside {
+                    hasher.usize(*other);
+                }
+            }
+        }
+        hasher.hash
+    }
+
+    // Restarts play from a copy of the current level that only keeps what is saved, so a replay
+    // of the recording starts from exactly the same state.
+    pub fn start_recording(&mut self){
+        let level = serde_json::to_value(&self.objects).unwrap();
+        self.objects = serde_json::from_value(level.clone()).unwrap();
+        self.static_version += 1;
+        self.rebuild_grid();
+        self.mode = Mode::Play;
+        self.recording = Some(Replay { level, physics: self.physics, frame: self.frame, cam: self.cam, screen: self.screen, inputs: Vec::new() });
+    }
+
+    pub fn save_recording(&self, path:&str) -> std::io::Result<()>{
+        if let Some(recording) = &self.recording {
+            let mut file = File::create(path)?;
+            file.write_all(serde_json::to_string(recording)?.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    // Plays a recording back, ignoring live input until it runs out.
+    pub fn load_replay(&mut self, path:&str) -> std::io::Result<()>{
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+        self.start_replay(serde_json::from_str(&contents)?)?;
+        Ok(())
+    }
+
+    fn start_replay(&mut self, replay:Replay) -> serde_json::Result<()>{
+        self.objects = serde_json::from_value(replay.level.clone())?;
+        upgrade_portals(&mut self.objects);
+        self.physics = replay.physics;
+        self.frame = replay.frame;
+        self.cam = replay.cam;
+        self.screen = replay.screen;
+        self.static_version += 1;
+        self.rebuild_grid();
+        self.mode = Mode::Play;
+        self.replay = Some((replay, 0));
+        Ok(())
+    }
+
+    // Called at the start of every played frame, either records the input or replaces it with the recorded one.
+    pub(super) fn step_input(&mut self){
+        if let Some((replay, index)) = &mut self.replay {
+            let Some(input) = replay.inputs.get(*index) else {
+                self.replay = None;
+                self.input.keys.clear();
+                return;
+            };
+            *index += 1;
+            self.input.keys = RECORDED_KEYS.iter().enumerate()
+                .filter(|(bit, _)| input.keys & (1 << bit) != 0)
+                .map(|(_, key)| *key)
+                .collect();
+            self.mouse_position = input.mouse;
+        }
+        else if let Some(recording) = &mut self.recording {
+            let keys = RECORDED_KEYS.iter().enumerate()
+                .filter(|(_, key)| self.input.is_pressed(key))
+                .fold(0, |keys, (bit, _)| keys | 1 << bit);
+            recording.inputs.push(InputFrame { keys, mouse: self.mouse_position });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ground, the player and a box that falls onto the ground next to it.
+    fn level() -> VectorGraphics{
+        let object = |controller:&str, gravity:f32, x:f32, y:f32, width:f32, height:f32| serde_json::json!({
+            "faction": 1, "controller": controller, "color": { "r": 0.5, "g": 0.5, "b": 0.5 },
+            "velocity": { "x": 0.0, "y": 0.0 }, "gravity": gravity, "direction": { "x": 0.0, "y": 0.0 },
+            "collision_type": "Bounce", "destroying": false, "destroy_at_frame": 0, "enable_firing_at_frame": 0,
+            "target": 0, "health": 0, "max_health": 0, "damage": 0, "disable_damage_bar_at_frame": 0,
+            "rect": { "x": x, "y": y, "width": width, "height": height },
+        });
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.resize(800.0, 600.0);
+        vector_graphics.objects = serde_json::from_value(serde_json::json!([
+            object("None", 0.0, 0.0, 500.0, 1600.0, 50.0),
+            object("Player", 0.3, 200.0, 419.0, 50.0, 80.0),
+            object("None", 0.3, 400.0, 300.0, 60.0, 60.0),
+        ])).unwrap();
+        vector_graphics
+    }
+
+    // Runs right, jumps halfway and lets go, recording the input.
+    fn record() -> VectorGraphics{
+        record_with(PhysicsConfig::default())
+    }
+
+    fn record_with(physics:PhysicsConfig) -> VectorGraphics{
+        let mut vector_graphics = level();
+        vector_graphics.physics = physics;
+        vector_graphics.start_recording();
+        for frame in 0..90{
+            match frame {
+                10 => vector_graphics.keydown(KeyCode::KeyD),
+                40 => vector_graphics.keydown(KeyCode::KeyW),
+                55 => vector_graphics.keyup(KeyCode::KeyW),
+                70 => vector_graphics.keyup(KeyCode::KeyD),
+                _ => {}
+            }
+            vector_graphics.update();
+        }
+        vector_graphics
+    }
+
+    fn replay(replay:Replay) -> u64{
+        let frames = replay.inputs.len();
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.start_replay(replay).unwrap();
+        for _ in 0..frames{
+            vector_graphics.update();
+        }
+        vector_graphics.state_hash()
+    }
+
+    #[test]
+    fn replay_matches_the_recording(){
+        let mut recorded = record();
+        let hash = recorded.state_hash();
+        let recording = recorded.recording.take().unwrap();
+        assert_eq!(recording.inputs.len(), 90);
+        assert_eq!(replay(recording), hash);
+    }
+
+    #[test]
+    fn changed_input_changes_the_hash(){
+        let mut recorded = record();
+        let hash = recorded.state_hash();
+        let mut recording = recorded.recording.take().unwrap();
+        // Let go of D a frame early.
+        recording.inputs[69].keys = 0;
+        assert_ne!(replay(recording), hash);
+    }
+
+    #[test]
+    fn replays_use_the_recorded_physics(){
+        // Slow enough falls that the jump lands differently than with the default.
+        let physics = PhysicsConfig { terminal_velocity: 4.0, ..PhysicsConfig::default() };
+        let mut recorded = record_with(physics);
+        let hash = recorded.state_hash();
+        let recording = recorded.recording.take().unwrap();
+        let saved = serde_json::to_string(&recording).unwrap();
+        assert_eq!(replay(serde_json::from_str(&saved).unwrap()), hash);
+        let mut default_physics:Replay = serde_json::from_str(&saved).unwrap();
+        default_physics.physics = PhysicsConfig::default();
+        assert_ne!(replay(default_physics), hash);
+    }
+
+    #[test]
+    fn the_hash_tells_apart_state_that_only_differs_in_options_and_flags(){
+        let hash = |change:&dyn Fn(&mut VectorGraphics)| {
+            let mut vector_graphics = level();
+            change(&mut vector_graphics);
+            vector_graphics.state_hash()
+        };
+        let unchanged = hash(&|_| {});
+        assert_ne!(hash(&|vector_graphics| vector_graphics.mode = Mode::Play), unchanged);
+        assert_ne!(hash(&|vector_graphics| vector_graphics.objects[2].standing_size = Some(Vector2 { x: 0.0, y: 0.0 })), unchanged);
+        let path = |forward:bool| Some(Path { forward, ..Path::new(Vector2 { x: 0.0, y: 0.0 }) });
+        assert_ne!(
+            hash(&|vector_graphics| vector_graphics.objects[0].path = path(false)),
+            hash(&|vector_graphics| vector_graphics.objects[0].path = path(true)),
+        );
+        let trigger = Trigger::new(trigger::TriggerAction::OpenDoor);
+        assert_ne!(
+            hash(&|vector_graphics| vector_graphics.objects[0].trigger = Some(Trigger { target: Some(1), ..trigger.clone() })),
+            hash(&|vector_graphics| vector_graphics.objects[0].trigger = Some(Trigger { target: Some(2), ..trigger.clone() })),
+        );
+    }
+}
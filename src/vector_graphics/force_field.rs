@@ -0,0 +1,220 @@
+use super::*;
+
+const DIAGONAL:f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+// The directions KeyY steps through, clockwise starting from up.
+const FORCE_DIRECTIONS:[Vector2; 8] = [
+    Vector2 { x: 0.0, y: -1.0 },
+    Vector2 { x: DIAGONAL, y: -DIAGONAL },
+    Vector2 { x: 1.0, y: 0.0 },
+    Vector2 { x: DIAGONAL, y: DIAGONAL },
+    Vector2 { x: 0.0, y: 1.0 },
+    Vector2 { x: -DIAGONAL, y: DIAGONAL },
+    Vector2 { x: -1.0, y: 0.0 },
+    Vector2 { x: -DIAGONAL, y: -DIAGONAL },
+];
+
+const FORCE_STRENGTH_STEP:f32 = 1.25;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub(super) enum ForceKind{
+    // Accelerates everything inside by strength every frame, for wind tunnels and updrafts.
+    Constant,
+    // Like Constant, but fading out towards the side the force points to, for fans.
+    Falloff,
+    // Sets the velocity along direction to at least strength, for jump pads.
+    Launch,
+    // A solid surface that moves whatever stands on it by strength every frame, for conveyor belts.
+    Surface,
+    // Replaces the world's gravity inside with direction times strength, for gravity flip zones.
+    Gravity,
+}
+
+// Force zones only act on objects with gravity, the same ones liquids act on.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub(super) struct ForceField{
+    pub kind:ForceKind,
+    // Unit vector.
+    pub direction:Vector2,
+    pub strength:f32,
+}
+
+impl ForceField{
+    fn new(kind:ForceKind) -> Self{
+        let (direction, strength) = match kind {
+            ForceKind::Constant => (FORCE_DIRECTIONS[0], 0.5),
+            ForceKind::Falloff => (FORCE_DIRECTIONS[0], 0.9),
+            ForceKind::Launch => (FORCE_DIRECTIONS[0], 18.0),
+            ForceKind::Surface => (FORCE_DIRECTIONS[2], 3.0),
+            ForceKind::Gravity => (FORCE_DIRECTIONS[0], 1.0),
+        };
+        ForceField { kind, direction, strength }
+    }
+
+    // Everything but conveyors is a zone that objects pass through.
+    pub fn is_zone(&self) -> bool{
+        !matches!(self.kind, ForceKind::Surface)
+    }
+}
+
+impl VectorGraphics {
+    // Adds the forces of the zones id is in to its velocity. Returns the velocity of the conveyor it stands on,
+    // which moves it along without becoming part of its own velocity.
+    pub(super) fn apply_forces(&mut self, id:usize) -> Vector2{
+        let mut carry = Vector2 { x: 0.0, y: 0.0 };
+        if self.objects[id].gravity == 0.0 {
+            return carry;
+        }
+        let center = self.objects[id].rect.center();
+        for other_id in self.overlaps(id) {
+            let Some(field) = self.objects[other_id].force else {
+                continue;
+            };
+            let zone = self.objects[other_id].rect;
+            let velocity = &mut self.objects[id].velocity;
+            match field.kind {
+                ForceKind::Constant => *velocity = velocity.add(field.direction.mul(field.strength)),
+                ForceKind::Falloff => {
+                    let reach = field.direction.x.abs() * zone.width / 2.0 + field.direction.y.abs() * zone.height / 2.0;
+                    let along = (center.sub(zone.center()).dot(field.direction) / reach).clamp(-1.0, 1.0);
+                    *velocity = velocity.add(field.direction.mul(field.strength * (1.0 - along) / 2.0));
+                }
+                ForceKind::Launch => {
+                    let along = velocity.dot(field.direction);
+                    if along < field.strength {
+                        *velocity = velocity.add(field.direction.mul(field.strength - along));
+                    }
+                }
+                ForceKind::Surface | ForceKind::Gravity => {}
+            }
+        }
+        match self.ground(id).and_then(|ground_id| self.objects[ground_id].force) {
+            Some(field) if !field.is_zone() => carry = field.direction.mul(field.strength),
+            _ => {}
+        }
+        carry
+    }
+
+    // KeyZ cycles an object between wind, fan, jump pad, conveyor, gravity zone and a plain block.
+    pub(super) fn cycle_force(&mut self, id:usize){
+        let object = &mut self.objects[id];
+        let kind = match object.force.map(|field| field.kind) {
+            None => Some(ForceKind::Constant),
+            Some(ForceKind::Constant) => Some(ForceKind::Falloff),
+            Some(ForceKind::Falloff) => Some(ForceKind::Launch),
+            Some(ForceKind::Launch) => Some(ForceKind::Surface),
+            Some(ForceKind::Surface) => Some(ForceKind::Gravity),
+            Some(ForceKind::Gravity) => None,
+        };
+        object.force = kind.map(ForceField::new);
+        let (collision_type, color) = match kind {
+            Some(ForceKind::Constant) => (CollisionType::None, Color { r: 0.75, g: 0.9, b: 1.0 }),
+            Some(ForceKind::Falloff) => (CollisionType::None, Color { r: 0.55, g: 0.75, b: 1.0 }),
+            Some(ForceKind::Launch) => (CollisionType::None, Color { r: 1.0, g: 0.85, b: 0.2 }),
+            Some(ForceKind::Surface) => (CollisionType::Bounce, Color { r: 0.35, g: 0.35, b: 0.4 }),
+            Some(ForceKind::Gravity) => (CollisionType::None, Color { r: 0.6, g: 0.35, b: 0.9 }),
+            None => (CollisionType::Bounce, Color { r: 0.025, g: 0.025, b: 0.025 }),
+        };
+        object.collision_type = collision_type;
+        object.color = color;
+        self.static_version += 1;
+    }
+
+    // KeyY turns the force of an object 45 degrees clockwise.
+    pub(super) fn turn_force(&mut self, id:usize){
+        let Some(field) = &mut self.objects[id].force else {
+            return;
+        };
+        let nearest = (0..FORCE_DIRECTIONS.len())
+            .max_by(|a, b| FORCE_DIRECTIONS[*a].dot(field.direction).total_cmp(&FORCE_DIRECTIONS[*b].dot(field.direction)))
+            .unwrap();
+        field.direction = FORCE_DIRECTIONS[(nearest + 1) % FORCE_DIRECTIONS.len()];
+    }
+
+    // KeyEqual and KeyMinus make the force of an object stronger or weaker.
+    pub(super) fn scale_force(&mut self, id:usize, stronger:bool){
+        let Some(field) = &mut self.objects[id].force else {
+            return;
+        };
+        field.strength = if stronger { field.strength * FORCE_STRENGTH_STEP } else { field.strength / FORCE_STRENGTH_STEP };
+    }
+
+    // An arrow per force, as long as it is strong, relative to the default strength of its kind.
+    pub(super) fn draw_forces(&self, geometry:&mut Geometry){
+        for object in &self.objects{
+            let Some(field) = &object.force else {
+                continue;
+            };
+            let center = object.rect.center();
+            let length = 40.0 * field.strength / ForceField::new(field.kind).strength;
+            let tip = center.add(field.direction.mul(length));
+            geometry.add_line(center.x, center.y, tip.x, tip.y, 3.0, 1.0, 1.0, 1.0);
+            geometry.add_sdf_quad(tip.x - 5.0, tip.y - 5.0, 10.0, 10.0, [1.0, 1.0, 1.0, 1.0], 5.0, 0.0, 0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(field:ForceField, collision_type:CollisionType) -> VectorGraphics{
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.add_object(Object {
+            force: Some(field),
+            collision_type,
+            ..Object::new(Rect { x: 0.0, y: 300.0, width: 400.0, height: 50.0 }, Color { r: 0.35, g: 0.35, b: 0.4 })
+        });
+        vector_graphics.add_object(Object::new(Rect { x: 0.0, y: 400.0, width: 1000.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }));
+        vector_graphics.add_object(Object {
+            gravity: 0.3,
+            ..Object::new(Rect { x: 20.0, y: 260.0 - CONTACT_SKIN, width: 40.0, height: 40.0 }, Color { r: 0.6, g: 0.4, b: 0.2 })
+        });
+        vector_graphics.mode = Mode::Play;
+        vector_graphics
+    }
+
+    #[test]
+    fn conveyors_carry_without_adding_momentum(){
+        let conveyor = ForceField::new(ForceKind::Surface);
+        let mut vector_graphics = level(conveyor, CollisionType::Bounce);
+        for _ in 0..30{
+            vector_graphics.update();
+        }
+        let object = &vector_graphics.objects[2];
+        assert!((object.rect.x - (20.0 + 30.0 * conveyor.strength)).abs() < 0.01);
+        assert_eq!(object.velocity.x, 0.0);
+        // Off the end it drops onto the ground below and stops there.
+        for _ in 0..200{
+            vector_graphics.update();
+        }
+        let x = vector_graphics.objects[2].rect.x;
+        assert!(x > 400.0 - 40.0 && vector_graphics.objects[2].rect.y > 350.0);
+        vector_graphics.update();
+        assert_eq!(vector_graphics.objects[2].rect.x, x);
+    }
+
+    #[test]
+    fn jump_pads_launch_at_their_strength(){
+        let pad = ForceField::new(ForceKind::Launch);
+        let mut vector_graphics = level(pad, CollisionType::None);
+        let mut fastest:f32 = 0.0;
+        for _ in 0..10{
+            vector_graphics.update();
+            fastest = fastest.max(-vector_graphics.objects[2].velocity.y);
+        }
+        assert!((fastest - pad.strength).abs() < 0.01);
+    }
+
+    #[test]
+    fn gravity_zones_turn_down_around(){
+        let flip = ForceField { strength: 0.3, ..ForceField::new(ForceKind::Gravity) };
+        let mut vector_graphics = level(flip, CollisionType::None);
+        vector_graphics.objects[2].rect.y = 305.0;
+        vector_graphics.rebuild_grid();
+        vector_graphics.update();
+        let object = &vector_graphics.objects[2];
+        assert!(object.down() == Vector2 { x: 0.0, y: -1.0 });
+        assert!(object.velocity.y < 0.0);
+    }
+}
@@ -0,0 +1,123 @@
+use super::*;
+use force_field::ForceKind;
+
+impl Object{
+    // Which way is down for the object, the axis its gravity mostly pulls along. Objects that aren't
+    // pulled anywhere keep the default of +y.
+    pub(super) fn down(&self) -> Vector2{
+        let direction = self.gravity_direction;
+        if direction.x.abs() > direction.y.abs() {
+            Vector2 { x: direction.x.signum(), y: 0.0 }
+        }
+        else if direction.y < 0.0 {
+            Vector2 { x: 0.0, y: -1.0 }
+        }
+        else {
+            Vector2 { x: 0.0, y: 1.0 }
+        }
+    }
+
+    // A thin rect just past the side of the object facing the axis aligned direction.
+    pub(super) fn side(&self, direction:Vector2) -> Rect{
+        let rect = self.rect;
+        let skin = CONTACT_SKIN*2.0;
+        if direction.y > 0.0 {
+            Rect { x: rect.x, y: rect.y + rect.height, width: rect.width, height: skin }
+        }
+        else if direction.y < 0.0 {
+            Rect { x: rect.x, y: rect.y - skin, width: rect.width, height: skin }
+        }
+        else if direction.x > 0.0 {
+            Rect { x: rect.x + rect.width, y: rect.y, width: skin, height: rect.height }
+        }
+        else {
+            Rect { x: rect.x - skin, y: rect.y, width: skin, height: rect.height }
+        }
+    }
+
+    // The side of the object that faces down.
+    pub(super) fn feet(&self) -> Rect{
+        self.side(self.down())
+    }
+}
+
+impl VectorGraphics {
+    // The gravity of the first gravity zone id is in, or the world's, before the object's own gravity scales it.
+    fn gravity_at(&self, id:usize) -> Vector2{
+        self.overlaps(id).into_iter()
+            .filter_map(|other_id| self.objects[other_id].force)
+            .find(|field| matches!(field.kind, ForceKind::Gravity))
+            .map(|field| field.direction.mul(field.strength))
+            .unwrap_or(self.gravity)
+    }
+
+    // Works out which way is down for id this frame and, unless it is climbing, accelerates it that way.
+    // Objects without gravity, including every static one, keep their last down and skip the zone query.
+    pub(super) fn apply_gravity(&mut self, id:usize){
+        if self.objects[id].gravity == 0.0 {
+            return;
+        }
+        let gravity = self.gravity_at(id);
+        let object = &mut self.objects[id];
+        object.gravity_direction = gravity;
+        if !object.climbing {
+            object.velocity = object.velocity.add(gravity.mul(object.gravity));
+        }
+    }
+
+    // Limits how fast the object can fall. Moving up isn't limited, so launches and knockback keep their speed.
+    pub(super) fn clamp_fall(&mut self, id:usize){
+        let object = &mut self.objects[id];
+        let down = object.down();
+        let fall = object.velocity.dot(down);
+        if fall > self.physics.terminal_velocity {
+            object.velocity = object.velocity.add(down.mul(self.physics.terminal_velocity - fall));
+        }
+    }
+
+    // Changes the size of a rect shaped object, keeping the side that faces down where it is and the object
+    // centered across it. Solid objects it already touches, like the ground, are fine, but it won't grow into
+    // anything else. Returns whether it could.
+    pub(super) fn reshape(&mut self, id:usize, size:Vector2) -> bool{
+        let old = self.objects[id].rect;
+        let down = self.objects[id].down();
+        let center = old.center();
+        let x = if down.x > 0.0 { old.x + old.width - size.x } else if down.x < 0.0 { old.x } else { center.x - size.x/2.0 };
+        let y = if down.y > 0.0 { old.y + old.height - size.y } else if down.y < 0.0 { old.y } else { center.y - size.y/2.0 };
+        let rect = Rect { x, y, width: size.x, height: size.y };
+        let current = self.objects[id].polygon();
+        let resized = vec![
+            Vector2 { x: rect.x, y: rect.y },
+            Vector2 { x: rect.x + rect.width, y: rect.y },
+            Vector2 { x: rect.x + rect.width, y: rect.y + rect.height },
+            Vector2 { x: rect.x, y: rect.y + rect.height },
+        ];
+        let blocked = self.overlap_rect(&rect, QueryFilter::all().of_collision_types(&[CollisionType::Bounce]).ignoring(id)).into_iter()
+            .any(|other_id| {
+                let other = self.objects[other_id].polygon();
+                polygon::collide(&resized, &other).is_some() && polygon::collide(&current, &other).is_none()
+            });
+        if blocked {
+            return false;
+        }
+        self.objects[id].rect = rect;
+        self.moved(id);
+        true
+    }
+
+    // Moves the object sideways first and then along its down axis, like slide_x followed by slide_y does
+    // with normal gravity. Returns whether it ended up standing on something.
+    pub(super) fn fall(&mut self, id:usize, motion:Vector2) -> bool{
+        let down = self.objects[id].down();
+        let falling = self.objects[id].velocity.dot(down) >= 0.0;
+        let landed = if down.x == 0.0 {
+            self.slide_x(id, motion.x);
+            self.slide_y(id, motion.y)
+        }
+        else {
+            self.slide_y(id, motion.y);
+            self.slide_x(id, motion.x)
+        };
+        landed && falling
+    }
+}
@@ -0,0 +1,308 @@
+use super::*;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub(super) enum JointKind{
+    // Keeps the anchors at most length apart.
+    Rope{ length:f32 },
+    // Pulls the anchors towards rest_length apart.
+    Spring{ rest_length:f32, stiffness:f32, damping:f32 },
+    // Keeps the anchors together, the objects can still turn around it.
+    Hinge,
+}
+
+// Links an anchor on the object that owns the joint to an anchor on other or, without other, to a point
+// in the world. Anchors are offsets from the center of an object, turning with it if it has a rigid body.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub(super) struct Joint{
+    pub kind:JointKind,
+    pub anchor:Vector2,
+    pub other:Option<usize>,
+    pub other_anchor:Vector2,
+}
+
+const JOINT_ITERATIONS:usize = 4;
+const SPRING_STIFFNESS:f32 = 0.05;
+const SPRING_DAMPING:f32 = 0.1;
+const JOINT_MASS:f32 = 1.0;
+
+fn rotate(v:Vector2, angle:f32) -> Vector2{
+    let (sin, cos) = sin_cos(angle);
+    Vector2 { x: v.x * cos - v.y * sin, y: v.x * sin + v.y * cos }
+}
+
+impl Joint{
+    // Keeps other pointing at the same object after the object at id is removed, returns false if the
+    // joint was attached to it.
+    pub fn removed(&mut self, id:usize) -> bool{
+        match self.other {
+            Some(other) if other == id => false,
+            Some(other) if other > id => {
+                self.other = Some(other - 1);
+                true
+            }
+            _ => true,
+        }
+    }
+
+    // KeyU cycles through the kinds, keeping the current distance between the anchors.
+    pub fn next_kind(&self, distance:f32) -> Option<JointKind>{
+        match self.kind {
+            JointKind::Rope{..} => Some(JointKind::Spring { rest_length: distance, stiffness: SPRING_STIFFNESS, damping: SPRING_DAMPING }),
+            JointKind::Spring{..} => Some(JointKind::Hinge),
+            JointKind::Hinge => None,
+        }
+    }
+}
+
+impl VectorGraphics {
+    // Joints move rigid bodies and anything else that isn't fixed in place.
+    fn joint_inverse_mass(&self, id:usize) -> f32{
+        let object = &self.objects[id];
+        match &object.body {
+            Some(body) => if body.pinned { 0.0 } else { 1.0 / body.mass },
+            None => {
+                if object.is_static() || matches!(object.controller, Controller::Platform | Controller::FollowTarget) { 0.0 } else { 1.0 / JOINT_MASS }
+            }
+        }
+    }
+
+    fn local_to_world(&self, id:usize, local:Vector2) -> Vector2{
+        let angle = self.objects[id].body.map(|body| body.angle).unwrap_or(0.0);
+        self.objects[id].rect.center().add(rotate(local, angle))
+    }
+
+    fn world_to_local(&self, id:usize, point:Vector2) -> Vector2{
+        let angle = self.objects[id].body.map(|body| body.angle).unwrap_or(0.0);
+        rotate(point.sub(self.objects[id].rect.center()), -angle)
+    }
+
+    // Where the two ends of the joint are in the world.
+    fn joint_ends(&self, id:usize, joint:&Joint) -> (Vector2, Vector2){
+        let end = match joint.other {
+            Some(other) => self.local_to_world(other, joint.other_anchor),
+            None => joint.other_anchor,
+        };
+        (self.local_to_world(id, joint.anchor), end)
+    }
+
+    fn point_velocity(&self, id:usize, point:Vector2) -> Vector2{
+        let offset = point.sub(self.objects[id].rect.center());
+        let angular_velocity = self.angular_velocity(id);
+        self.objects[id].velocity.add(Vector2 { x: -angular_velocity * offset.y, y: angular_velocity * offset.x })
+    }
+
+    fn push_at(&mut self, id:usize, impulse:Vector2, point:Vector2){
+        if self.objects[id].body.is_some() {
+            let offset = point.sub(self.objects[id].rect.center());
+            self.apply_impulse(id, impulse, offset);
+        }
+        else {
+            let inverse_mass = self.joint_inverse_mass(id);
+            self.objects[id].velocity = self.objects[id].velocity.add(impulse.mul(inverse_mass));
+        }
+    }
+
+    // How easily the object moves and turns when pushed at point along direction.
+    fn joint_mobility(&self, id:usize, point:Vector2, direction:Vector2) -> (f32, f32){
+        let inverse_inertia = match &self.objects[id].body {
+            Some(body) => 1.0 / body.inertia(),
+            None => 0.0,
+        };
+        let offset = point.sub(self.objects[id].rect.center());
+        let arm = offset.x * direction.y - offset.y * direction.x;
+        (self.joint_inverse_mass(id), arm * arm * inverse_inertia)
+    }
+
+    // Moves the object by a share of error along direction, split between moving and turning it.
+    fn shift(&mut self, id:usize, point:Vector2, direction:Vector2, error:f32, total:f32){
+        let (linear, angular) = self.joint_mobility(id, point, direction);
+        let correction = direction.mul(error * linear / total);
+        if self.objects[id].body.is_some() {
+            let offset = point.sub(self.objects[id].rect.center());
+            let arm = offset.x * direction.y - offset.y * direction.x;
+            let center = self.objects[id].rect.center().add(correction);
+            if let Some(body) = &mut self.objects[id].body {
+                if arm != 0.0 {
+                    body.angle += error * angular / total / arm;
+                }
+            }
+            self.place_body(id, center);
+        }
+        else {
+            self.slide_x(id, correction.x);
+            self.slide_y(id, correction.y);
+        }
+    }
+
+    fn solve_joint(&mut self, id:usize, joint:&Joint, apply_spring:bool){
+        let (start, end) = self.joint_ends(id, joint);
+        let delta = end.sub(start);
+        let distance = delta.magnitude();
+        if distance < 1.0e-4 {
+            return;
+        }
+        let direction = delta.mul(1.0 / distance);
+        let (linear, angular) = self.joint_mobility(id, start, direction);
+        let (other_linear, other_angular) = joint.other.map(|other| self.joint_mobility(other, end, direction)).unwrap_or((0.0, 0.0));
+        let total = linear + angular + other_linear + other_angular;
+        if total == 0.0 {
+            return;
+        }
+        let other_velocity = joint.other.map(|other| self.point_velocity(other, end)).unwrap_or(Vector2 { x: 0.0, y: 0.0 });
+        // Positive when the anchors are moving apart.
+        let separating = other_velocity.sub(self.point_velocity(id, start)).dot(direction);
+        let (error, impulse) = match joint.kind {
+            JointKind::Rope { length } => {
+                if distance <= length {
+                    return;
+                }
+                (distance - length, separating.max(0.0) / total)
+            }
+            JointKind::Hinge => (distance, separating / total),
+            JointKind::Spring { rest_length, stiffness, damping } => {
+                if !apply_spring {
+                    return;
+                }
+                (0.0, (stiffness * (distance - rest_length) + damping * separating) / total)
+            }
+        };
+        self.push_at(id, direction.mul(impulse), start);
+        if let Some(other) = joint.other {
+            self.push_at(other, direction.mul(-impulse), end);
+        }
+        if error > 0.0 {
+            self.shift(id, start, direction, error, total);
+            if let Some(other) = joint.other {
+                self.shift(other, end, direction.mul(-1.0), error, total);
+            }
+        }
+    }
+
+    // Springs push once a frame, ropes and hinges are solved a few times so chains of them settle.
+    pub(super) fn solve_joints(&mut self){
+        for iteration in 0..JOINT_ITERATIONS{
+            for id in 0..self.objects.len(){
+                for index in 0..self.objects[id].joints.len(){
+                    let joint = self.objects[id].joints[index];
+                    self.solve_joint(id, &joint, iteration == 0);
+                }
+            }
+        }
+    }
+
+    pub(super) fn draw_joints(&self, geometry:&mut Geometry){
+        for (id, object) in self.objects.iter().enumerate(){
+            for joint in &object.joints{
+                let (start, end) = self.joint_ends(id, joint);
+                let (thickness, r, g, b) = match joint.kind {
+                    JointKind::Rope{..} => (3.0, 0.55, 0.4, 0.2),
+                    JointKind::Spring{..} => (2.0, 0.7, 0.7, 0.75),
+                    JointKind::Hinge => (2.0, 0.3, 0.3, 0.3),
+                };
+                geometry.add_line(start.x, start.y, end.x, end.y, thickness, r, g, b);
+                geometry.add_sdf_quad(end.x - 4.0, end.y - 4.0, 8.0, 8.0, [r, g, b, 1.0], 4.0, 0.0, 0.0);
+            }
+        }
+    }
+
+    // KeyH on the first object remembers where it was pressed, KeyH again links it with a rope to the second
+    // object or, over empty space, to that point in the world.
+    pub(super) fn hook(&mut self, point:Vector2){
+        let other = self.find_object_at_point(point);
+        match self.joint_start.take() {
+            None => {
+                if let Some(id) = other {
+                    self.joint_start = Some((id, self.world_to_local(id, point)));
+                }
+            }
+            Some((id, anchor)) => {
+                if other == Some(id) || id >= self.objects.len() {
+                    return;
+                }
+                let start = self.local_to_world(id, anchor);
+                let other_anchor = match other {
+                    Some(other) => self.world_to_local(other, point),
+                    None => point,
+                };
+                self.objects[id].joints.push(Joint { kind: JointKind::Rope { length: point.sub(start).magnitude() }, anchor, other, other_anchor });
+            }
+        }
+    }
+
+    pub(super) fn cycle_joint(&mut self, id:usize){
+        let Some(joint) = self.objects[id].joints.last().copied() else {
+            return;
+        };
+        let (start, end) = self.joint_ends(id, &joint);
+        match joint.next_kind(end.sub(start).magnitude()) {
+            Some(kind) => {
+                if let Some(last) = self.objects[id].joints.last_mut() {
+                    last.kind = kind;
+                }
+            }
+            None => {
+                self.objects[id].joints.pop();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A falling box with a joint from its top to a point in the world 100 pixels above it.
+    fn hang(kind:JointKind, body:bool) -> VectorGraphics{
+        let rect = Rect { x: 180.0, y: 200.0, width: 40.0, height: 40.0 };
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.add_object(Object {
+            gravity: 0.3,
+            body: body.then(|| RigidBody::new(Vector2 { x: rect.width, y: rect.height }, 1.0, false)),
+            joints: vec![Joint { kind, anchor: Vector2 { x: 0.0, y: -20.0 }, other: None, other_anchor: Vector2 { x: 200.0, y: 100.0 } }],
+            ..Object::new(rect, Color { r: 0.6, g: 0.4, b: 0.2 })
+        });
+        vector_graphics.mode = Mode::Play;
+        vector_graphics
+    }
+
+    fn length(vector_graphics:&VectorGraphics) -> f32{
+        let (start, end) = vector_graphics.joint_ends(0, &vector_graphics.objects[0].joints[0]);
+        end.sub(start).magnitude()
+    }
+
+    #[test]
+    fn ropes_hold_their_length_but_can_go_slack(){
+        let mut vector_graphics = hang(JointKind::Rope { length: 150.0 }, false);
+        // Slack to start with, so the box falls freely.
+        vector_graphics.update();
+        assert!(vector_graphics.objects[0].velocity.y > 0.0 && length(&vector_graphics) < 150.0);
+        for _ in 0..200{
+            vector_graphics.update();
+            assert!(length(&vector_graphics) < 150.0 + 0.1);
+        }
+        assert!(length(&vector_graphics) > 149.0);
+    }
+
+    #[test]
+    fn springs_settle_where_they_hold_the_weight(){
+        let mut vector_graphics = hang(JointKind::Spring { rest_length: 100.0, stiffness: SPRING_STIFFNESS, damping: SPRING_DAMPING }, false);
+        for _ in 0..600{
+            vector_graphics.update();
+        }
+        let stretch = 0.3 / SPRING_STIFFNESS;
+        assert!((length(&vector_graphics) - 100.0 - stretch).abs() < 0.5, "settled at {}", length(&vector_graphics));
+    }
+
+    #[test]
+    fn hinges_keep_the_anchors_together_while_bodies_turn(){
+        let mut vector_graphics = hang(JointKind::Hinge, true);
+        // Off center, so the box swings around the hinge.
+        vector_graphics.objects[0].joints[0].anchor = Vector2 { x: -20.0, y: -20.0 };
+        vector_graphics.objects[0].joints[0].other_anchor = Vector2 { x: 180.0, y: 200.0 };
+        for _ in 0..60{
+            vector_graphics.update();
+            assert!(length(&vector_graphics) < 0.5);
+        }
+        assert!(vector_graphics.objects[0].body.unwrap().angle.abs() > 0.1);
+    }
+}
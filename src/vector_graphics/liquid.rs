@@ -0,0 +1,208 @@
+use super::*;
+
+// Liquids don't block anything. Objects inside them fall slower, are slowed down and pushed up, all
+// scaled by how much of the object is submerged. Objects with health take damage unless their faction is spared.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub(super) struct Liquid{
+    pub gravity_scale:f32,
+    pub drag:f32,
+    pub buoyancy:f32,
+    pub damage:i32,
+    #[serde(default)]
+    pub spares:Vec<u32>,
+}
+
+pub(super) const LIQUID_WATER:Liquid = Liquid { gravity_scale: 0.3, drag: 0.08, buoyancy: 0.25, damage: 0, spares: Vec::new() };
+pub(super) const LIQUID_LAVA:Liquid = Liquid { gravity_scale: 0.5, drag: 0.15, buoyancy: 0.1, damage: 5, spares: Vec::new() };
+pub(super) const LIQUID_ALPHA:f32 = 0.5;
+const LIQUID_DAMAGE_INTERVAL:usize = 30;
+const SWIM_STROKE_FRAMES:usize = 15;
+
+impl VectorGraphics {
+    // The liquid the object is in and the fraction of it that is submerged, measured along its down axis.
+    fn liquid_at(&self, id:usize) -> Option<(usize, f32)>{
+        let rect = self.objects[id].rect;
+        let vertical = self.objects[id].down().x == 0.0;
+        self.overlaps(id).into_iter()
+            .filter(|other_id| self.objects[*other_id].liquid.is_some())
+            .map(|other_id| {
+                let liquid = self.objects[other_id].rect;
+                let (start, size, liquid_start, liquid_size) = if vertical {
+                    (rect.y, rect.height, liquid.y, liquid.height)
+                }
+                else {
+                    (rect.x, rect.width, liquid.x, liquid.width)
+                };
+                let overlap = (start + size).min(liquid_start + liquid_size) - start.max(liquid_start);
+                (other_id, (overlap / size).clamp(0.0, 1.0))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    pub(super) fn apply_liquid(&mut self, id:usize){
+        if self.objects[id].gravity == 0.0 {
+            return;
+        }
+        let Some((liquid_id, submerged)) = self.liquid_at(id) else {
+            return;
+        };
+        let Some(liquid) = self.objects[liquid_id].liquid.clone() else {
+            return;
+        };
+        let object = &mut self.objects[id];
+        let lift = (object.gravity * (1.0 - liquid.gravity_scale) + liquid.buoyancy) * submerged;
+        object.velocity = object.velocity.sub(object.down().mul(lift));
+        object.velocity = object.velocity.mul(1.0 - liquid.drag * submerged);
+        if liquid.damage > 0 && !liquid.spares.contains(&object.faction) && self.frame % LIQUID_DAMAGE_INTERVAL == 0 {
+            // Invulnerability from the last tick would swallow every other one.
+            self.lose_health(id, liquid.damage);
+        }
+    }
+
+    // Holding jump while in a liquid swims up with a stroke every few frames.
+    pub(super) fn swim(&mut self, id:usize){
+        if self.liquid_at(id).is_none() || self.objects[id].swim_stroke_at_frame > self.frame {
+            return;
+        }
+        self.objects[id].swim_stroke_at_frame = self.frame + SWIM_STROKE_FRAMES;
+        let object = &mut self.objects[id];
+        let down = object.down();
+        object.velocity = object.velocity.sub(down.mul(object.velocity.dot(down) + self.movement.swim_force));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lava_hurts_every_damage_interval(){
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.add_object(Object { 
+            liquid: Some(LIQUID_LAVA), 
+            collision_type: CollisionType::None, 
+            ..Object::new(Rect { x: 0.0, y: 0.0, width: 200.0, height: 200.0 }, Color { r: 1.0, g: 0.35, b: 0.05 })
+        });
+        vector_graphics.add_object(Object::new(Rect { x: 0.0, y: 200.0, width: 200.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }));
+        vector_graphics.add_object(Object {
+            faction: FACTION_PLAYER,
+            gravity: 0.3,
+            health: 1000,
+            max_health: 1000,
+            ..Object::new(Rect { x: 80.0, y: 150.0, width: 40.0, height: 40.0 }, Color { r: 1.0, g: 0.5, b: 0.0 })
+        });
+        vector_graphics.mode = Mode::Play;
+        let mut ticks = Vec::new();
+        for _ in 0..LIQUID_DAMAGE_INTERVAL*10{
+            let health = vector_graphics.objects[2].health;
+            let frame = vector_graphics.frame;
+            vector_graphics.update();
+            if vector_graphics.objects[2].health < health {
+                assert_eq!(health - vector_graphics.objects[2].health, LIQUID_LAVA.damage);
+                ticks.push(frame);
+            }
+        }
+        assert_eq!(ticks.len(), 10);
+        assert!(ticks.windows(2).all(|pair| pair[1] - pair[0] == LIQUID_DAMAGE_INTERVAL));
+    }
+
+    #[test]
+    fn submersion_is_measured_along_down(){
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.add_object(Object {
+            liquid: Some(LIQUID_WATER),
+            collision_type: CollisionType::None,
+            ..Object::new(Rect { x: 0.0, y: 0.0, width: 100.0, height: 400.0 }, Color { r: 0.1, g: 0.3, b: 1.0 })
+        });
+        vector_graphics.add_object(Object {
+            gravity: 0.3,
+            ..Object::new(Rect { x: 80.0, y: 100.0, width: 40.0, height: 40.0 }, Color { r: 1.0, g: 0.5, b: 0.0 })
+        });
+        // All of its height is in the water, but only half of its width.
+        vector_graphics.objects[1].gravity_direction = Vector2 { x: 0.0, y: 1.0 };
+        assert_eq!(vector_graphics.liquid_at(1), Some((0, 1.0)));
+        vector_graphics.objects[1].gravity_direction = Vector2 { x: -1.0, y: 0.0 };
+        assert_eq!(vector_graphics.liquid_at(1), Some((0, 0.5)));
+    }
+
+    // A pool of the liquid 400 deep, with ground under it.
+    fn pool(liquid:Liquid) -> VectorGraphics{
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.add_object(Object {
+            liquid: Some(liquid),
+            collision_type: CollisionType::None,
+            ..Object::new(Rect { x: 0.0, y: 0.0, width: 400.0, height: 400.0 }, Color { r: 0.1, g: 0.3, b: 1.0 })
+        });
+        vector_graphics.add_object(Object::new(Rect { x: 0.0, y: 400.0, width: 400.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }));
+        vector_graphics.mode = Mode::Play;
+        vector_graphics
+    }
+
+    fn swimmer(x:f32, y:f32, faction:u32) -> Object{
+        Object {
+            faction,
+            gravity: 0.3,
+            health: 100,
+            max_health: 100,
+            ..Object::new(Rect { x, y, width: 40.0, height: 40.0 }, Color { r: 1.0, g: 0.5, b: 0.0 })
+        }
+    }
+
+    #[test]
+    fn lava_only_hurts_factions_it_does_not_spare(){
+        let mut vector_graphics = pool(Liquid { spares: vec![FACTION_PLAYER], ..LIQUID_LAVA });
+        vector_graphics.add_object(swimmer(50.0, 350.0, FACTION_PLAYER));
+        vector_graphics.add_object(swimmer(250.0, 350.0, FACTION_ENEMY));
+        for _ in 0..LIQUID_DAMAGE_INTERVAL{
+            vector_graphics.update();
+        }
+        assert_eq!(vector_graphics.objects[2].health, 100);
+        assert_eq!(vector_graphics.objects[3].health, 100 - LIQUID_LAVA.damage);
+    }
+
+    #[test]
+    fn buoyant_objects_float_partly_submerged(){
+        let mut vector_graphics = pool(LIQUID_WATER);
+        vector_graphics.add_object(swimmer(180.0, 300.0, FACTION_ENEMY));
+        for _ in 0..600{
+            vector_graphics.update();
+        }
+        // Where the lift on the submerged part balances gravity.
+        let gravity = vector_graphics.objects[2].gravity;
+        let balance = gravity / (gravity * (1.0 - LIQUID_WATER.gravity_scale) + LIQUID_WATER.buoyancy);
+        let (_, submerged) = vector_graphics.liquid_at(2).unwrap();
+        assert!((submerged - balance).abs() < 0.05, "{} submerged instead of {}", submerged, balance);
+        assert!(vector_graphics.objects[2].velocity.y.abs() < 0.05);
+    }
+
+    #[test]
+    fn drag_scales_with_how_submerged_the_object_is(){
+        let mut vector_graphics = pool(LIQUID_WATER);
+        vector_graphics.add_object(Object { velocity: Vector2 { x: 10.0, y: 0.0 }, ..swimmer(100.0, -20.0, FACTION_ENEMY) });
+        vector_graphics.add_object(Object { velocity: Vector2 { x: 10.0, y: 0.0 }, ..swimmer(200.0, 100.0, FACTION_ENEMY) });
+        vector_graphics.apply_liquid(2);
+        vector_graphics.apply_liquid(3);
+        assert!((vector_graphics.objects[2].velocity.x - 10.0 * (1.0 - LIQUID_WATER.drag * 0.5)).abs() < 0.001);
+        assert!((vector_graphics.objects[3].velocity.x - 10.0 * (1.0 - LIQUID_WATER.drag)).abs() < 0.001);
+    }
+
+    #[test]
+    fn swimming_strokes_up_every_few_frames_in_liquids_only(){
+        let mut vector_graphics = pool(LIQUID_WATER);
+        vector_graphics.add_object(Object { velocity: Vector2 { x: 0.0, y: 3.0 }, ..swimmer(100.0, 200.0, FACTION_PLAYER) });
+        vector_graphics.add_object(Object { velocity: Vector2 { x: 0.0, y: 3.0 }, ..swimmer(500.0, 200.0, FACTION_PLAYER) });
+        let swim_force = vector_graphics.movement.swim_force;
+        vector_graphics.swim(2);
+        vector_graphics.swim(3);
+        assert_eq!(vector_graphics.objects[2].velocity.y, -swim_force);
+        assert_eq!(vector_graphics.objects[3].velocity.y, 3.0);
+        // The next stroke waits for the last one to finish.
+        vector_graphics.objects[2].velocity.y = 1.0;
+        vector_graphics.frame += SWIM_STROKE_FRAMES - 1;
+        vector_graphics.swim(2);
+        assert_eq!(vector_graphics.objects[2].velocity.y, 1.0);
+        vector_graphics.frame += 1;
+        vector_graphics.swim(2);
+        assert_eq!(vector_graphics.objects[2].velocity.y, -swim_force);
+    }
+}
@@ -0,0 +1,124 @@
+use super::*;
+
+const MINIMAP_WIDTH:f32 = 240.0;
+const MINIMAP_HEIGHT:f32 = 160.0;
+const MINIMAP_MARGIN:f32 = 16.0;
+const MINIMAP_PADDING:f32 = 100.0;
+const MINIMAP_MIN_MARKER:f32 = 3.0;
+// The outline of the camera view, which is drawn last and always gets room.
+const MINIMAP_FRAME_RECTS:usize = 4;
+
+pub struct MinimapView{
+    pub viewport:(f32,f32,f32,f32),
+    pub view:(f32,f32,f32,f32),
+}
+
+impl VectorGraphics {
+    fn level_bounds(&self) -> Rect{
+        let mut min = Vector2 { x: self.cam.x, y: self.cam.y };
+        let mut max = Vector2 { x: self.cam.x + self.screen.x, y: self.cam.y + self.screen.y };
+        for object in &self.objects{
+            min.x = min.x.min(object.rect.x);
+            min.y = min.y.min(object.rect.y);
+            max.x = max.x.max(object.rect.x + object.rect.width);
+            max.y = max.y.max(object.rect.y + object.rect.height);
+        }
+        Rect {
+            x: min.x - MINIMAP_PADDING,
+            y: min.y - MINIMAP_PADDING,
+            width: max.x - min.x + MINIMAP_PADDING*2.0,
+            height: max.y - min.y + MINIMAP_PADDING*2.0,
+        }
+    }
+
+    // Widens or heightens the bounds around their center so the world isn't stretched in the minimap.
+    fn fit_aspect(bounds:Rect, width:f32, height:f32) -> Rect{
+        let aspect = width / height;
+        let center = bounds.center();
+        let mut result = bounds;
+        if bounds.width / bounds.height < aspect {
+            result.width = bounds.height * aspect;
+        }
+        else{
+            result.height = bounds.width / aspect;
+        }
+        result.x = center.x - result.width/2.0;
+        result.y = center.y - result.height/2.0;
+        result
+    }
+
+    fn minimap_color(object:&Object) -> Color{
+        match object.controller {
+            Controller::Player | Controller::AI => {
+                match object.faction {
+                    FACTION_PLAYER => Color { r: 0.2, g: 0.6, b: 1.0 },
+                    FACTION_ENEMY => Color { r: 1.0, g: 0.1, b: 0.1 },
+                    _ => Color { r: 1.0, g: 1.0, b: 1.0 },
+                }
+            }
+            _ => Color { r: object.color.r*0.5 + 0.3, g: object.color.g*0.5 + 0.3, b: object.color.b*0.5 + 0.3 },
+        }
+    }
+
+    // Where the minimap goes on screen, if it is shown and fits.
+    fn minimap_viewport(&self) -> Option<Rect>{
+        let viewport_x = self.screen.x - MINIMAP_WIDTH - MINIMAP_MARGIN;
+        if !self.show_minimap || viewport_x < 0.0 || self.screen.y < MINIMAP_HEIGHT + MINIMAP_MARGIN {
+            return None;
+        }
+        Some(Rect { x: viewport_x, y: MINIMAP_MARGIN, width: MINIMAP_WIDTH, height: MINIMAP_HEIGHT })
+    }
+
+    // The drop shadow and border around the minimap panel are part of the main view,
+    // so they are placed in world coordinates relative to the camera.
+    pub(super) fn draw_minimap_frame(&self, geometry:&mut Geometry){
+        if let Some(viewport) = self.minimap_viewport() {
+            let x = self.cam.x + viewport.x;
+            let y = self.cam.y + viewport.y;
+            geometry.add_sdf_quad(x + 4.0, y + 6.0, viewport.width, viewport.height, [0.0, 0.0, 0.0, 0.6], 6.0, 0.0, 10.0);
+            geometry.add_sdf_quad(x - 3.0, y - 3.0, viewport.width + 6.0, viewport.height + 6.0, [0.9, 0.9, 0.9, 1.0], 6.0, 3.0, 0.0);
+        }
+    }
+
+    // Fills the geometry in world coordinates and returns where and through which view to draw it.
+    pub fn draw_minimap(&self, geometry:&mut Geometry) -> Option<MinimapView>{
+        let viewport = self.minimap_viewport()?;
+        let view = Self::fit_aspect(self.level_bounds(), viewport.width, viewport.height);
+        let pixel = view.width / viewport.width;
+
+        geometry.add_rect(view.x, view.y, view.width, view.height, 0.1, 0.1, 0.1);
+        // Anything smaller than a minimap pixel is left out and the biggest objects go first, so a level
+        // with more objects than the geometry holds loses its least visible ones.
+        let mut terrain:Vec<&Object> = self.objects.iter()
+            .filter(|object| object.trigger.is_none() && !matches!(object.controller, Controller::Player | Controller::AI))
+            .filter(|object| object.rect.width >= pixel || object.rect.height >= pixel)
+            .collect();
+        terrain.sort_by(|a, b| (b.rect.width * b.rect.height).total_cmp(&(a.rect.width * a.rect.height)));
+        for object in terrain{
+            if !geometry.has_room(4 * (MINIMAP_FRAME_RECTS + 1), 6 * (MINIMAP_FRAME_RECTS + 1)) {
+                break;
+            }
+            let color = Self::minimap_color(object);
+            geometry.add_rect(object.rect.x, object.rect.y, object.rect.width, object.rect.height, color.r, color.g, color.b);
+        }
+        // Characters are drawn as dots that stay visible however far the minimap is zoomed out.
+        for object in self.objects.iter().filter(|object| object.trigger.is_none() && matches!(object.controller, Controller::Player | Controller::AI)){
+            let color = Self::minimap_color(object);
+            let center = object.rect.center();
+            let size = object.rect.width.max(object.rect.height).max(MINIMAP_MIN_MARKER * pixel);
+            geometry.add_sdf_quad(center.x - size/2.0, center.y - size/2.0, size, size, [color.r, color.g, color.b, 1.0], size/2.0, 0.0, 0.0);
+        }
+
+        let frame = Rect { x: self.cam.x, y: self.cam.y, width: self.screen.x, height: self.screen.y };
+        let thickness = pixel * 1.5;
+        geometry.add_rect(frame.x, frame.y, frame.width, thickness, 1.0, 1.0, 1.0);
+        geometry.add_rect(frame.x, frame.y + frame.height - thickness, frame.width, thickness, 1.0, 1.0, 1.0);
+        geometry.add_rect(frame.x, frame.y, thickness, frame.height, 1.0, 1.0, 1.0);
+        geometry.add_rect(frame.x + frame.width - thickness, frame.y, thickness, frame.height, 1.0, 1.0, 1.0);
+
+        Some(MinimapView { 
+            viewport: (viewport.x, viewport.y, viewport.width, viewport.height), 
+            view: (view.x, view.y, view.width, view.height),
+        })
+    }
+}
@@ -0,0 +1,363 @@
+use super::*;
+use winit::keyboard::KeyCode;
+
+// Tunables of how characters move. Speeds are in pixels per frame, accelerations in pixels per frame
+// per frame and times in frames. The player uses all of it, AI only runs and jumps.
+#[derive(Clone, Copy)]
+pub(super) struct MovementConfig{
+    pub run_speed:f32,
+    pub ground_acceleration:f32,
+    pub ground_deceleration:f32,
+    // Used instead of the acceleration while running against the current velocity.
+    pub turn_acceleration:f32,
+    // Scales the accelerations in the air.
+    pub air_control:f32,
+    pub jump_speed:f32,
+    // How much of the upward speed is kept when jump is let go of early.
+    pub jump_cut:f32,
+    // A jump still works this long after running off a ledge.
+    pub coyote_frames:usize,
+    // A jump pressed this long before landing still happens on landing.
+    pub jump_buffer_frames:usize,
+    pub wall_slide_speed:f32,
+    // A wall jump pushes this fast away from the wall and ignores steering for wall_jump_frames.
+    pub wall_jump_push:f32,
+    pub wall_jump_frames:usize,
+    // One dash per jump, along the input or the way the player faces, without gravity.
+    pub dash_speed:f32,
+    pub dash_frames:usize,
+    // Crouching shrinks the height, or the width with sideways gravity, to this fraction of standing.
+    pub crouch_scale:f32,
+    pub crawl_speed:f32,
+    pub climb_speed:f32,
+    pub swim_force:f32,
+}
+
+impl Default for MovementConfig{
+    fn default() -> Self{
+        MovementConfig {
+            run_speed: 5.0,
+            ground_acceleration: 1.0,
+            ground_deceleration: 1.5,
+            turn_acceleration: 2.5,
+            air_control: 0.65,
+            jump_speed: 14.0,
+            jump_cut: 0.5,
+            coyote_frames: 6,
+            jump_buffer_frames: 6,
+            wall_slide_speed: 2.5,
+            wall_jump_push: 6.0,
+            wall_jump_frames: 10,
+            dash_speed: 12.0,
+            dash_frames: 8,
+            crouch_scale: 0.5,
+            crawl_speed: 2.0,
+            climb_speed: 3.0,
+            swim_force: 6.0,
+        }
+    }
+}
+
+// What the player controller remembers between frames.
+#[derive(Clone, Copy, Default)]
+pub(super) struct MovementState{
+    pub grounded_at_frame:Option<usize>,
+    pub jump_pressed_at_frame:Option<usize>,
+    pub jump_held:bool,
+    pub dash_held:bool,
+    // From a jump until it is cut short or starts falling.
+    pub jumping:bool,
+    pub can_dash:bool,
+    pub dash_until_frame:usize,
+    pub dash_direction:f32,
+    pub wall_jump_until_frame:usize,
+    pub facing:f32,
+}
+
+impl VectorGraphics {
+    // Whether the object is touching a wall on the given side, -1 or 1 across its down axis.
+    fn touching_wall(&self, id:usize, side:f32) -> bool{
+        let object = &self.objects[id];
+        let down = object.down();
+        let probe = object.side(Vector2 { x: down.y.abs(), y: down.x.abs() }.mul(side));
+        !self.overlap_rect(&probe, QueryFilter::all().of_collision_types(&[CollisionType::Bounce]).ignoring(id)).is_empty()
+    }
+
+    // Shrinks the object along its down axis when crouch is set and stands it back up once it isn't and there is room.
+    // Returns whether the object is crouching.
+    fn crouch(&mut self, id:usize, crouch:bool) -> bool{
+        let object = &self.objects[id];
+        if !matches!(object.shape, Shape::Rect) || object.body.is_some() {
+            return false;
+        }
+        let standing_size = object.standing_size;
+        match standing_size {
+            None if crouch => {
+                let size = Vector2 { x: object.rect.width, y: object.rect.height };
+                let down = object.down();
+                let along = Vector2 { x: down.x.abs(), y: down.y.abs() };
+                let crouched = size.sub(along.mul(size.dot(along) * (1.0 - self.movement.crouch_scale)));
+                if self.reshape(id, crouched) {
+                    self.objects[id].standing_size = Some(size);
+                }
+            }
+            Some(size) if !crouch && self.reshape(id, size) => {
+                self.objects[id].standing_size = None;
+            }
+            _ => {}
+        }
+        self.objects[id].standing_size.is_some()
+    }
+
+    // Accelerates the velocity across the down axis towards speed. Ground friction scales the acceleration
+    // and sticky ground also lowers the top speed, like apply_friction does.
+    fn run(&mut self, id:usize, speed:f32, grounded:bool){
+        let config = self.movement;
+        let friction = if grounded { self.friction(id) } else { 1.0 };
+        let object = &mut self.objects[id];
+        let down = object.down();
+        let across = Vector2 { x: down.y.abs(), y: down.x.abs() };
+        let target = speed / friction.max(1.0);
+        let speed = object.velocity.dot(across);
+        let rate = if target == 0.0 {
+            config.ground_deceleration
+        } else if speed * target < 0.0 {
+            config.turn_acceleration
+        } else {
+            config.ground_acceleration
+        };
+        let rate = rate * if grounded { friction.min(1.0) } else { config.air_control };
+        object.velocity = object.velocity.add(across.mul((target - speed).clamp(-rate, rate)));
+    }
+
+    // The player controller. W jumps, with coyote time and a buffer, and jumps lower when let go early.
+    // It also jumps off walls, drops through one way platforms together with S and swims in liquids.
+    // A and D run, sliding down walls they push against, S crouches and left shift dashes.
+    pub(super) fn move_player(&mut self, id:usize, grounded:bool){
+        let config = self.movement;
+        let frame = self.frame;
+        let mut state = self.objects[id].movement;
+        let left = self.input.is_pressed(&KeyCode::KeyA);
+        let right = self.input.is_pressed(&KeyCode::KeyD);
+        let input = if left == right { 0.0 } else if left { -1.0 } else { 1.0 };
+        let jump = self.input.is_pressed(&KeyCode::KeyW);
+        let dash = self.input.is_pressed(&KeyCode::ShiftLeft);
+        let down = self.objects[id].down();
+        let across = Vector2 { x: down.y.abs(), y: down.x.abs() };
+        let crouching = self.crouch(id, grounded && !jump && self.input.is_pressed(&KeyCode::KeyS));
+        if input != 0.0 {
+            state.facing = input;
+        }
+        if grounded {
+            state.grounded_at_frame = Some(frame);
+            state.can_dash = true;
+        }
+        if jump && !state.jump_held {
+            state.jump_pressed_at_frame = Some(frame);
+        }
+
+        let fall = self.objects[id].velocity.dot(down);
+        if state.jumping && (!jump || fall >= 0.0) {
+            if fall < 0.0 {
+                self.objects[id].velocity = self.objects[id].velocity.add(down.mul(fall * config.jump_cut - fall));
+            }
+            state.jumping = false;
+        }
+
+        if state.jump_pressed_at_frame.is_some_and(|at| frame <= at + config.jump_buffer_frames) {
+            let on_ground = state.grounded_at_frame.is_some_and(|at| frame <= at + config.coyote_frames);
+            let wall = [-1.0, 1.0].into_iter().find(|side| self.touching_wall(id, *side));
+            if on_ground && grounded && self.input.is_pressed(&KeyCode::KeyS) && self.standing_on_one_way(id) {
+                self.objects[id].drop_through_until_frame = frame + DROP_THROUGH_FRAMES;
+                state.jump_pressed_at_frame = None;
+            }
+            else if on_ground && !crouching {
+                let velocity = self.objects[id].velocity;
+                self.objects[id].velocity = velocity.sub(down.mul(velocity.dot(down) + config.jump_speed));
+                state.grounded_at_frame = None;
+                state.jump_pressed_at_frame = None;
+                state.jumping = true;
+            }
+            else if let Some(side) = wall {
+                self.objects[id].velocity = across.mul(-side * config.wall_jump_push).sub(down.mul(config.jump_speed));
+                state.wall_jump_until_frame = frame + config.wall_jump_frames;
+                state.jump_pressed_at_frame = None;
+                state.jumping = true;
+            }
+        }
+        if jump && !grounded {
+            self.swim(id);
+        }
+
+        if dash && !state.dash_held && state.can_dash && frame >= state.dash_until_frame {
+            state.dash_until_frame = frame + config.dash_frames;
+            state.dash_direction = if input != 0.0 { input } else { state.facing.signum() };
+            state.can_dash = false;
+            state.jumping = false;
+        }
+        if frame < state.dash_until_frame {
+            self.objects[id].velocity = across.mul(state.dash_direction * config.dash_speed);
+        }
+        else if frame >= state.wall_jump_until_frame {
+            self.run(id, input * if crouching { config.crawl_speed } else { config.run_speed }, grounded);
+            let fall = self.objects[id].velocity.dot(down);
+            if !grounded && input != 0.0 && fall > config.wall_slide_speed && self.touching_wall(id, input) {
+                self.objects[id].velocity = self.objects[id].velocity.add(down.mul(config.wall_slide_speed - fall));
+            }
+        }
+        state.jump_held = jump;
+        state.dash_held = dash;
+        self.objects[id].movement = state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(objects:Vec<Object>) -> VectorGraphics{
+        let mut vector_graphics = VectorGraphics::new();
+        for object in objects{
+            vector_graphics.add_object(object);
+        }
+        vector_graphics.mode = Mode::Play;
+        vector_graphics
+    }
+
+    fn ground(rect:Rect) -> Object{
+        Object::new(rect, Color { r: 0.0, g: 0.0, b: 0.0 })
+    }
+
+    fn player(x:f32, y:f32) -> Object{
+        Object {
+            faction: FACTION_PLAYER,
+            controller: Controller::Player,
+            gravity: 0.3,
+            ..Object::new(Rect { x, y, width: 40.0, height: 40.0 }, Color { r: 1.0, g: 0.5, b: 0.0 })
+        }
+    }
+
+    fn update_for(vector_graphics:&mut VectorGraphics, frames:usize){
+        for _ in 0..frames{
+            vector_graphics.update();
+        }
+    }
+
+    // Runs off the end of a ledge and presses jump the given number of frames after leaving it.
+    // Returns whether the player went up.
+    fn jump_after_leaving_ledge(frames:usize) -> bool{
+        let mut vector_graphics = level(vec![
+            ground(Rect { x: 0.0, y: 400.0, width: 200.0, height: 50.0 }),
+            player(150.0, 360.0 - CONTACT_SKIN),
+        ]);
+        vector_graphics.keydown(KeyCode::KeyD);
+        while vector_graphics.objects[1].grounded || vector_graphics.frame == 0 {
+            vector_graphics.update();
+        }
+        update_for(&mut vector_graphics, frames);
+        vector_graphics.keydown(KeyCode::KeyW);
+        vector_graphics.update();
+        vector_graphics.objects[1].velocity.y < 0.0
+    }
+
+    #[test]
+    fn jumps_work_for_a_moment_after_leaving_a_ledge(){
+        let coyote_frames = MovementConfig::default().coyote_frames;
+        assert!(jump_after_leaving_ledge(0));
+        assert!(jump_after_leaving_ledge(coyote_frames - 2));
+        assert!(!jump_after_leaving_ledge(coyote_frames + 2));
+    }
+
+    // Drops the player onto the ground pressing jump the given number of frames before it lands.
+    // Returns whether it jumped on landing.
+    fn jump_before_landing(frames:usize) -> bool{
+        let objects = || vec![
+            ground(Rect { x: 0.0, y: 400.0, width: 200.0, height: 50.0 }),
+            player(80.0, 200.0),
+        ];
+        let mut vector_graphics = level(objects());
+        while !vector_graphics.objects[1].grounded {
+            vector_graphics.update();
+        }
+        let landing = vector_graphics.frame;
+        let mut vector_graphics = level(objects());
+        update_for(&mut vector_graphics, landing - frames);
+        vector_graphics.keydown(KeyCode::KeyW);
+        update_for(&mut vector_graphics, frames + 2);
+        vector_graphics.objects[1].velocity.y < 0.0
+    }
+
+    #[test]
+    fn jumps_pressed_just_before_landing_happen_on_landing(){
+        let jump_buffer_frames = MovementConfig::default().jump_buffer_frames;
+        assert!(jump_before_landing(jump_buffer_frames - 2));
+        assert!(!jump_before_landing(jump_buffer_frames + 3));
+    }
+
+    // How high the player gets holding jump for the given number of frames.
+    fn jump_height(held:usize) -> f32{
+        let mut vector_graphics = level(vec![
+            ground(Rect { x: 0.0, y: 400.0, width: 200.0, height: 50.0 }),
+            player(80.0, 360.0 - CONTACT_SKIN),
+        ]);
+        vector_graphics.update();
+        vector_graphics.keydown(KeyCode::KeyW);
+        let mut top = vector_graphics.objects[1].rect.y;
+        for frame in 0..120{
+            if frame == held {
+                vector_graphics.keyup(KeyCode::KeyW);
+            }
+            vector_graphics.update();
+            top = top.min(vector_graphics.objects[1].rect.y);
+        }
+        360.0 - top
+    }
+
+    #[test]
+    fn letting_go_of_jump_early_jumps_lower(){
+        let full = jump_height(120);
+        let short = jump_height(5);
+        assert!(full > 250.0 && short < full / 2.0, "{} and {}", full, short);
+    }
+
+    #[test]
+    fn crouching_players_stay_down_under_low_ceilings(){
+        let mut vector_graphics = level(vec![
+            ground(Rect { x: 0.0, y: 400.0, width: 800.0, height: 50.0 }),
+            // Leaves room to crawl under, but not to stand.
+            ground(Rect { x: 200.0, y: 300.0, width: 200.0, height: 70.0 }),
+            player(100.0, 360.0 - CONTACT_SKIN),
+        ]);
+        let height = |vector_graphics:&VectorGraphics| vector_graphics.objects[2].rect.height;
+        vector_graphics.update();
+        vector_graphics.keydown(KeyCode::KeyS);
+        vector_graphics.keydown(KeyCode::KeyD);
+        vector_graphics.update();
+        assert_eq!(height(&vector_graphics), 20.0);
+        assert!((vector_graphics.objects[2].rect.y + 20.0 - 400.0).abs() < 0.1);
+        while vector_graphics.objects[2].rect.x < 260.0 && vector_graphics.frame < 300 {
+            vector_graphics.update();
+        }
+        assert!(vector_graphics.objects[2].rect.x >= 260.0);
+
+        // Letting go of S under the ceiling, or jumping, doesn't stand up.
+        vector_graphics.keyup(KeyCode::KeyS);
+        vector_graphics.keyup(KeyCode::KeyD);
+        update_for(&mut vector_graphics, 10);
+        assert_eq!(height(&vector_graphics), 20.0);
+        vector_graphics.keydown(KeyCode::KeyW);
+        update_for(&mut vector_graphics, 2);
+        vector_graphics.keyup(KeyCode::KeyW);
+        assert_eq!(height(&vector_graphics), 20.0);
+        assert!(vector_graphics.objects[2].velocity.y >= 0.0);
+
+        // Out from under it, it stands back up.
+        vector_graphics.keydown(KeyCode::KeyD);
+        while vector_graphics.objects[2].rect.x < 420.0 && vector_graphics.frame < 600 {
+            vector_graphics.update();
+        }
+        vector_graphics.update();
+        assert_eq!(height(&vector_graphics), 40.0);
+        assert!((vector_graphics.objects[2].rect.y + 40.0 - 400.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,227 @@
+use super::*;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub(super) enum PathMode{
+    Loop,
+    PingPong,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub(super) enum Easing{
+    Linear,
+    EaseInOut,
+}
+
+// Waypoints are positions of the top left of the platform's rect. The segment, progress and forward
+// fields are where the platform currently is along the path.
+#[derive(Serialize, Deserialize, Clone)]
+pub(super) struct Path{
+    pub waypoints:Vec<Vector2>,
+    pub speed:f32,
+    pub mode:PathMode,
+    pub easing:Easing,
+    #[serde(default)]
+    pub segment:usize,
+    #[serde(default)]
+    pub progress:f32,
+    #[serde(default)]
+    pub forward:bool,
+}
+
+const PLATFORM_SPEED:f32 = 2.0;
+
+impl Path{
+    pub fn new(start:Vector2) -> Self{
+        Path { 
+            waypoints: vec![start], 
+            speed: PLATFORM_SPEED, 
+            mode: PathMode::PingPong, 
+            easing: Easing::Linear, 
+            segment: 0, 
+            progress: 0.0, 
+            forward: true,
+        }
+    }
+
+    // Cycles through every combination of mode and easing, used by the editor.
+    pub fn next_style(&mut self){
+        (self.mode, self.easing) = match (self.mode, self.easing) {
+            (PathMode::PingPong, Easing::Linear) => (PathMode::Loop, Easing::Linear),
+            (PathMode::Loop, Easing::Linear) => (PathMode::PingPong, Easing::EaseInOut),
+            (PathMode::PingPong, Easing::EaseInOut) => (PathMode::Loop, Easing::EaseInOut),
+            (PathMode::Loop, Easing::EaseInOut) => (PathMode::PingPong, Easing::Linear),
+        };
+        self.reset();
+    }
+
+    pub fn reset(&mut self){
+        self.segment = 0;
+        self.progress = 0.0;
+        self.forward = true;
+    }
+
+    fn ease(&self, t:f32) -> f32{
+        match self.easing {
+            Easing::Linear => t,
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+
+    fn next_index(&self, index:usize) -> usize{
+        let last = self.waypoints.len() - 1;
+        match self.mode {
+            PathMode::Loop => (index + 1) % self.waypoints.len(),
+            PathMode::PingPong => {
+                if self.forward { (index + 1).min(last) } else { index.saturating_sub(1) }
+            }
+        }
+    }
+
+    // Moves along the path by speed and returns the new position.
+    pub fn advance(&mut self) -> Option<Vector2>{
+        if self.waypoints.len() < 2 {
+            return self.waypoints.first().copied();
+        }
+        self.segment = self.segment.min(self.waypoints.len() - 1);
+        let from = self.waypoints[self.segment];
+        let to = self.waypoints[self.next_index(self.segment)];
+        let length = to.sub(from).magnitude();
+        self.progress += if length > 0.0 { self.speed / length } else { 1.0 };
+        if self.progress >= 1.0 {
+            self.progress = 0.0;
+            self.segment = self.next_index(self.segment);
+            if self.mode == PathMode::PingPong && (self.segment == 0 || self.segment == self.waypoints.len() - 1) {
+                self.forward = self.segment == 0;
+            }
+            return Some(self.waypoints[self.segment]);
+        }
+        Some(from.add(to.sub(from).mul(self.ease(self.progress))))
+    }
+}
+
+impl VectorGraphics {
+    fn is_riding(&self, id:usize, platform_id:usize) -> bool{
+        let feet = self.objects[id].feet();
+        self.objects[id].grounded && Rect::overlaps(&feet, &self.objects[platform_id].rect)
+    }
+
+    // Moves the platform along one axis and pushes whatever it runs into out of the way.
+    fn move_platform_axis(&mut self, id:usize, motion:Vector2, riders:&[usize]){
+        self.objects[id].rect.x += motion.x;
+        self.objects[id].rect.y += motion.y;
+        self.moved(id);
+        let platform = self.objects[id].rect;
+        for other_id in self.grid.query(&platform){
+            let other = &self.objects[other_id];
+            if other_id == id || riders.contains(&other_id) || other.is_static() 
+                || !matches!(other.collision_type, CollisionType::Bounce) || !Rect::overlaps(&platform, &other.rect) {
+                continue;
+            }
+            if motion.x > 0.0 {
+                self.slide_x(other_id, platform.x + platform.width - other.rect.x + CONTACT_SKIN);
+            }
+            else if motion.x < 0.0 {
+                self.slide_x(other_id, platform.x - (other.rect.x + other.rect.width) - CONTACT_SKIN);
+            }
+            else if motion.y > 0.0 {
+                self.slide_y(other_id, platform.y + platform.height - other.rect.y + CONTACT_SKIN);
+            }
+            else if motion.y < 0.0 {
+                self.slide_y(other_id, platform.y - (other.rect.y + other.rect.height) - CONTACT_SKIN);
+            }
+        }
+    }
+
+    // Kinematic platforms follow their path regardless of terrain, carry the objects standing on them
+    // and push the ones they move into.
+    pub(super) fn update_platform(&mut self, id:usize){
+        let Some(position) = self.objects[id].path.as_mut().and_then(|path| path.advance()) else {
+            return;
+        };
+        let delta = Vector2 { x: position.x - self.objects[id].rect.x, y: position.y - self.objects[id].rect.y };
+        let riders:Vec<usize> = self.grid.query(&self.objects[id].rect.expand(CONTACT_SKIN*2.0))
+            .into_iter()
+            .filter(|other_id| *other_id != id && self.is_riding(*other_id, id))
+            .collect();
+        self.move_platform_axis(id, Vector2 { x: delta.x, y: 0.0 }, &riders);
+        self.move_platform_axis(id, Vector2 { x: 0.0, y: delta.y }, &riders);
+        for rider in riders{
+            self.slide_x(rider, delta.x);
+            self.slide_y(rider, delta.y);
+        }
+    }
+
+    pub(super) fn draw_paths(&self, geometry:&mut Geometry){
+        for object in &self.objects{
+            let Some(path) = &object.path else {
+                continue;
+            };
+            let offset = Vector2 { x: object.rect.width/2.0, y: object.rect.height/2.0 };
+            for (index, waypoint) in path.waypoints.iter().enumerate(){
+                let center = waypoint.add(offset);
+                let next = match path.mode {
+                    PathMode::Loop => Some(path.waypoints[(index + 1) % path.waypoints.len()]),
+                    PathMode::PingPong => path.waypoints.get(index + 1).copied(),
+                };
+                if let Some(next) = next {
+                    let next = next.add(offset);
+                    geometry.add_line(center.x, center.y, next.x, next.y, 2.0, 0.2, 0.6, 1.0);
+                }
+                geometry.add_sdf_quad(center.x - 6.0, center.y - 6.0, 12.0, 12.0, [0.2, 0.6, 1.0, 1.0], 6.0, 0.0, 0.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A platform on a path through the given waypoints with a crate resting on it.
+    fn level(waypoints:Vec<Vector2>) -> VectorGraphics{
+        let start = waypoints[0];
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.add_object(Object {
+            controller: Controller::Platform,
+            path: Some(Path { waypoints, ..Path::new(start) }),
+            ..Object::new(Rect { x: start.x, y: start.y, width: 100.0, height: 20.0 }, Color { r: 0.3, g: 0.3, b: 0.3 })
+        });
+        vector_graphics.add_object(Object {
+            gravity: 0.3,
+            ..Object::new(Rect { x: start.x + 30.0, y: start.y - 40.0 - CONTACT_SKIN, width: 40.0, height: 40.0 }, Color { r: 0.6, g: 0.4, b: 0.2 })
+        });
+        vector_graphics.mode = Mode::Play;
+        vector_graphics
+    }
+
+    // Where the crate is relative to the platform.
+    fn offset(vector_graphics:&VectorGraphics) -> Vector2{
+        let platform = vector_graphics.objects[0].rect;
+        let rider = vector_graphics.objects[1].rect;
+        Vector2 { x: rider.x - platform.x, y: rider.y + rider.height - platform.y }
+    }
+
+    #[test]
+    fn riders_move_with_the_platform(){
+        let mut vector_graphics = level(vec![Vector2 { x: 0.0, y: 300.0 }, Vector2 { x: 200.0, y: 300.0 }]);
+        vector_graphics.update();
+        let start = offset(&vector_graphics);
+        for _ in 0..60{
+            vector_graphics.update();
+        }
+        assert!((vector_graphics.objects[0].rect.x - 122.0).abs() < 0.01);
+        let end = offset(&vector_graphics);
+        assert!((end.x - start.x).abs() < 0.1 && end.y.abs() < CONTACT_SKIN*2.0);
+    }
+
+    #[test]
+    fn riders_stay_on_platforms_going_up_and_down(){
+        let mut vector_graphics = level(vec![Vector2 { x: 0.0, y: 300.0 }, Vector2 { x: 0.0, y: 100.0 }]);
+        // Up to the top, then most of the way back down.
+        for _ in 0..180{
+            vector_graphics.update();
+            assert!(offset(&vector_graphics).y.abs() < CONTACT_SKIN*2.0);
+        }
+        assert!(vector_graphics.objects[0].rect.y > 200.0);
+    }
+}
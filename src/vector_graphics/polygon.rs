@@ -0,0 +1,209 @@
+use super::Vector2;
+
+// Result of a separating axis test. normal is the direction to push the first polygon to separate it
+// from the second, depth is how far, and contacts are the points where the two touch.
+pub struct Manifold{
+    pub normal:Vector2,
+    pub depth:f32,
+    pub contacts:Vec<Vector2>,
+}
+
+fn centroid(points:&[Vector2]) -> Vector2{
+    let mut sum = Vector2 { x: 0.0, y: 0.0 };
+    for point in points{
+        sum = sum.add(*point);
+    }
+    sum.mul(1.0 / points.len() as f32)
+}
+
+// Outward facing unit normal of the edge starting at index, regardless of the winding order.
+fn edge_normal(points:&[Vector2], index:usize, center:Vector2) -> Vector2{
+    let start = points[index];
+    let end = points[(index + 1) % points.len()];
+    let edge = end.sub(start);
+    let normal = Vector2 { x: edge.y, y: -edge.x }.normalize();
+    let midpoint = start.add(end).mul(0.5);
+    if normal.dot(midpoint.sub(center)) < 0.0 {
+        return normal.mul(-1.0);
+    }
+    normal
+}
+
+fn project(points:&[Vector2], axis:Vector2) -> (f32, f32){
+    let mut min = f32::INFINITY;
+    let mut max = f32::NEG_INFINITY;
+    for point in points{
+        let projection = point.dot(axis);
+        min = min.min(projection);
+        max = max.max(projection);
+    }
+    (min, max)
+}
+
+// Keeps the part of the segment on the side of the plane where dot(point, normal) <= offset.
+fn clip(segment:&[Vector2], normal:Vector2, offset:f32) -> Vec<Vector2>{
+    let mut result:Vec<Vector2> = Vec::new();
+    if segment.len() < 2 {
+        return result;
+    }
+    let distance_a = segment[0].dot(normal) - offset;
+    let distance_b = segment[1].dot(normal) - offset;
+    if distance_a <= 0.0 {
+        result.push(segment[0]);
+    }
+    if distance_b <= 0.0 {
+        result.push(segment[1]);
+    }
+    if distance_a * distance_b < 0.0 {
+        let t = distance_a / (distance_a - distance_b);
+        result.push(segment[0].add(segment[1].sub(segment[0]).mul(t)));
+    }
+    result
+}
+
+// Separating axis test between two convex polygons, with the contact points found by clipping
+// the incident edge against the reference edge of the axis of least penetration.
+pub fn collide(a:&[Vector2], b:&[Vector2]) -> Option<Manifold>{
+    if a.len() < 3 || b.len() < 3 {
+        return None;
+    }
+    let center_a = centroid(a);
+    let center_b = centroid(b);
+    let mut depth = f32::INFINITY;
+    let mut axis = Vector2 { x: 0.0, y: 0.0 };
+    let mut reference_on_a = false;
+    let mut reference_edge = 0;
+    for (polygon, center, on_a) in [(a, center_a, true), (b, center_b, false)]{
+        for index in 0..polygon.len(){
+            let normal = edge_normal(polygon, index, center);
+            let (min_a, max_a) = project(a, normal);
+            let (min_b, max_b) = project(b, normal);
+            // How far the other polygon reaches past this edge. Measuring it from the edge side only keeps
+            // the opposite edge of a box, which overlaps just as much, from winning and flipping the normal.
+            let overlap = if on_a { max_a - min_b } else { max_b - min_a };
+            if overlap <= 0.0 {
+                return None;
+            }
+            if overlap < depth {
+                depth = overlap;
+                axis = normal;
+                reference_on_a = on_a;
+                reference_edge = index;
+            }
+        }
+    }
+
+    let (reference, incident, incident_center) = if reference_on_a { (a, b, center_b) } else { (b, a, center_a) };
+    let reference_normal = edge_normal(reference, reference_edge, if reference_on_a { center_a } else { center_b });
+    let mut incident_edge = 0;
+    let mut most_opposed = f32::INFINITY;
+    for index in 0..incident.len(){
+        let alignment = edge_normal(incident, index, incident_center).dot(reference_normal);
+        if alignment < most_opposed {
+            most_opposed = alignment;
+            incident_edge = index;
+        }
+    }
+    let reference_start = reference[reference_edge];
+    let reference_end = reference[(reference_edge + 1) % reference.len()];
+    let tangent = reference_end.sub(reference_start).normalize();
+    let mut contacts = vec![incident[incident_edge], incident[(incident_edge + 1) % incident.len()]];
+    contacts = clip(&contacts, tangent.mul(-1.0), -reference_start.dot(tangent));
+    contacts = clip(&contacts, tangent, reference_end.dot(tangent));
+    contacts.retain(|point| point.sub(reference_start).dot(reference_normal) <= 0.0);
+
+    // The axis is the outward normal of the reference edge, flip it when that edge belongs to a.
+    let normal = if reference_on_a { axis.mul(-1.0) } else { axis };
+    Some(Manifold { normal, depth, contacts })
+}
+
+// Where a ray from origin along the unit direction enters the convex polygon, as the distance along the ray
+// and the outward normal of the edge it crosses. Rays starting inside the polygon don't hit it.
+pub fn raycast(points:&[Vector2], origin:Vector2, direction:Vector2, max_distance:f32) -> Option<(f32, Vector2)>{
+    if points.len() < 3 {
+        return None;
+    }
+    let center = centroid(points);
+    let mut enter = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = Vector2 { x: 0.0, y: 0.0 };
+    for index in 0..points.len(){
+        let edge = edge_normal(points, index, center);
+        // Positive while the origin is outside of this edge.
+        let outside = origin.sub(points[index]).dot(edge);
+        let speed = direction.dot(edge);
+        if speed == 0.0 {
+            if outside > 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let distance = -outside / speed;
+        if speed < 0.0 {
+            if distance > enter {
+                enter = distance;
+                normal = edge;
+            }
+        }
+        else {
+            exit = exit.min(distance);
+        }
+    }
+    if enter > exit || enter < 0.0 || enter > max_distance {
+        return None;
+    }
+    Some((enter, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x:f32, y:f32, width:f32, height:f32) -> Vec<Vector2>{
+        vec![
+            Vector2 { x, y },
+            Vector2 { x: x + width, y },
+            Vector2 { x: x + width, y: y + height },
+            Vector2 { x, y: y + height },
+        ]
+    }
+
+    fn close(a:Vector2, b:Vector2) -> bool{
+        (a.x - b.x).abs() < 0.001 && (a.y - b.y).abs() < 0.001
+    }
+
+    #[test]
+    fn overlapping_boxes_are_pushed_apart_along_the_shallowest_axis(){
+        let manifold = collide(&rect(0.0, 0.0, 10.0, 10.0), &rect(8.0, 2.0, 10.0, 6.0)).unwrap();
+        assert!(close(manifold.normal, Vector2 { x: -1.0, y: 0.0 }));
+        assert!((manifold.depth - 2.0).abs() < 0.001);
+        // The edge of the second box that is inside the first, clipped to the edge it went through.
+        assert_eq!(manifold.contacts.len(), 2);
+        assert!(manifold.contacts.iter().any(|contact| close(*contact, Vector2 { x: 8.0, y: 2.0 })));
+        assert!(manifold.contacts.iter().any(|contact| close(*contact, Vector2 { x: 8.0, y: 8.0 })));
+
+        // Swapped around the normal flips.
+        let manifold = collide(&rect(8.0, 2.0, 10.0, 6.0), &rect(0.0, 0.0, 10.0, 10.0)).unwrap();
+        assert!(close(manifold.normal, Vector2 { x: 1.0, y: 0.0 }));
+    }
+
+    #[test]
+    fn a_corner_poking_into_an_edge_touches_at_the_corner(){
+        let spike = vec![Vector2 { x: 3.0, y: -6.0 }, Vector2 { x: 7.0, y: -6.0 }, Vector2 { x: 5.0, y: 2.0 }];
+        let manifold = collide(&rect(0.0, 0.0, 10.0, 10.0), &spike).unwrap();
+        assert!(close(manifold.normal, Vector2 { x: 0.0, y: 1.0 }));
+        assert!((manifold.depth - 2.0).abs() < 0.001);
+        assert_eq!(manifold.contacts.len(), 1);
+        assert!(close(manifold.contacts[0], Vector2 { x: 5.0, y: 2.0 }));
+    }
+
+    #[test]
+    fn separated_and_touching_polygons_do_not_collide(){
+        assert!(collide(&rect(0.0, 0.0, 10.0, 10.0), &rect(11.0, 0.0, 10.0, 10.0)).is_none());
+        assert!(collide(&rect(0.0, 0.0, 10.0, 10.0), &rect(10.0, 0.0, 10.0, 10.0)).is_none());
+        // Overlapping bounding boxes, but a gap along the diagonal.
+        let triangle = vec![Vector2 { x: 6.0, y: 11.0 }, Vector2 { x: 11.0, y: 6.0 }, Vector2 { x: 11.0, y: 11.0 }];
+        let other = vec![Vector2 { x: 0.0, y: 0.0 }, Vector2 { x: 10.0, y: 0.0 }, Vector2 { x: 0.0, y: 10.0 }];
+        assert!(collide(&other, &triangle).is_none());
+    }
+}
@@ -0,0 +1,321 @@
+use super::*;
+
+// How long an object ignores portals after going through one, so it can't bounce between a pair.
+const PORTAL_COOLDOWN_FRAMES:usize = 20;
+
+const PORTAL_FACINGS:[Vector2; 4] = [
+    Vector2 { x: 0.0, y: -1.0 },
+    Vector2 { x: 1.0, y: 0.0 },
+    Vector2 { x: 0.0, y: 1.0 },
+    Vector2 { x: -1.0, y: 0.0 },
+];
+
+// Portals with the same name are linked, the editor makes sure no more than two share one. Old saves
+// can have several entrances sharing one exit only portal.
+// Objects come out in front of the other portal with their velocity turned to match its facing.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub(super) struct Portal{
+    pub name:String,
+    // Unit vector pointing out of the front of the portal.
+    pub facing:Vector2,
+    // Can only be come out of, which makes the link one-way.
+    pub exit_only:bool,
+}
+
+impl Portal{
+    fn new(name:String) -> Self{
+        Portal { name, facing: PORTAL_FACINGS[0], exit_only: false }
+    }
+}
+
+// Old saves link portals with CollisionType::PortalTo and the target id. They become a named one-way pair
+// facing opposite ways, so objects keep moving the way they went in like they used to. Entrances that
+// went to the same target share the name the exit got from the first of them.
+pub(super) fn upgrade_portals(objects:&mut [Object]){
+    for id in 0..objects.len(){
+        if !matches!(objects[id].collision_type, CollisionType::PortalTo) {
+            continue;
+        }
+        let target = objects[id].target;
+        let exit_name = objects.get(target)
+            .and_then(|exit| exit.portal.as_ref())
+            .filter(|portal| portal.exit_only)
+            .map(|portal| portal.name.clone());
+        let name = exit_name.clone().unwrap_or_else(|| format!("portal {}", id));
+        objects[id].collision_type = CollisionType::None;
+        objects[id].portal = Some(Portal { name: name.clone(), facing: PORTAL_FACINGS[0], exit_only: false });
+        match objects.get_mut(target) {
+            Some(exit) if exit_name.is_none() => exit.portal = Some(Portal { name, facing: PORTAL_FACINGS[2], exit_only: true }),
+            _ => {}
+        }
+    }
+}
+
+impl VectorGraphics {
+    // The other portal with the same name, the exit only one when several entrances share it.
+    fn linked_portal(&self, portal_id:usize) -> Option<usize>{
+        let name = &self.objects[portal_id].portal.as_ref()?.name;
+        let named = |id:&usize| *id != portal_id && self.objects[*id].portal.as_ref().is_some_and(|portal| portal.name == *name);
+        let exit_only = |id:&usize| self.objects[*id].portal.as_ref().is_some_and(|portal| portal.exit_only);
+        (0..self.objects.len()).filter(named).find(exit_only)
+            .or_else(|| (0..self.objects.len()).find(named))
+    }
+
+    fn unused_portal_name(&self) -> String{
+        (1..).map(|n| format!("portal {}", n))
+            .find(|name| !self.objects.iter().any(|object| object.portal.as_ref().is_some_and(|portal| portal.name == *name)))
+            .unwrap()
+    }
+
+    // Moves id from the portal it overlaps to the front of the linked one, turning its velocity by the
+    // angle between going into the first and coming out of the second. Returns false if the exit is blocked.
+    pub(super) fn enter_portal(&mut self, id:usize, portal_id:usize) -> bool{
+        if self.objects[id].portal.is_some() || self.objects[id].portal_cooldown_until_frame > self.frame {
+            return false;
+        }
+        let Some(entrance) = self.objects[portal_id].portal.clone() else {
+            return false;
+        };
+        let Some(exit_id) = self.linked_portal(portal_id) else {
+            return false;
+        };
+        if entrance.exit_only {
+            return false;
+        }
+        let Some(exit) = self.objects[exit_id].portal.clone() else {
+            return false;
+        };
+        // Rotation taking the way in, against the entrance facing, to the exit facing, as a complex number.
+        let into = entrance.facing.mul(-1.0);
+        let (cos, sin) = (into.dot(exit.facing), into.x * exit.facing.y - into.y * exit.facing.x);
+        let velocity = self.objects[id].velocity;
+        let old_rect = self.objects[id].rect;
+        let exit_rect = self.objects[exit_id].rect;
+        let distance = exit_rect.extent(exit.facing) + old_rect.extent(exit.facing) + CONTACT_SKIN;
+        let location = exit_rect.center().add(exit.facing.mul(distance));
+        self.objects[id].rect.x = location.x - old_rect.width/2.0;
+        self.objects[id].rect.y = location.y - old_rect.height/2.0;
+        self.moved(id);
+        for other_id in self.overlaps(id) {
+            self.apply_damage(id, other_id);
+            if matches!(self.objects[other_id].collision_type, CollisionType::Bounce) {
+                self.objects[id].rect = old_rect;
+                self.moved(id);
+                return false;
+            }
+        }
+        self.objects[id].velocity = Vector2 { x: velocity.x * cos - velocity.y * sin, y: velocity.x * sin + velocity.y * cos };
+        self.objects[id].portal_cooldown_until_frame = self.frame + PORTAL_COOLDOWN_FRAMES;
+        true
+    }
+
+    fn set_portal_color(&mut self, id:usize){
+        let exit_only = self.objects[id].portal.as_ref().is_some_and(|portal| portal.exit_only);
+        self.objects[id].color = if exit_only { Color { r: 0.2, g: 0.2, b: 1.0 } } else { Color { r: 0.2, g: 1.0, b: 0.2 } };
+        self.static_version += 1;
+    }
+
+    // KeyI on an object makes it a portal and selects it. KeyI on the selected portal turns it clockwise,
+    // after facing left it goes back to being a block and its link is gone.
+    pub(super) fn edit_portal(&mut self, id:usize){
+        if self.objects[id].portal.is_none() {
+            let name = self.unused_portal_name();
+            self.objects[id].portal = Some(Portal::new(name));
+            self.objects[id].collision_type = CollisionType::None;
+            self.set_portal_color(id);
+            self.last_portal = Some(id);
+            return;
+        }
+        if self.last_portal != Some(id) {
+            self.last_portal = Some(id);
+            return;
+        }
+        let object = &mut self.objects[id];
+        let Some(portal) = &mut object.portal else {
+            return;
+        };
+        match PORTAL_FACINGS.iter().position(|facing| *facing == portal.facing) {
+            Some(index) if index + 1 < PORTAL_FACINGS.len() => portal.facing = PORTAL_FACINGS[index + 1],
+            _ => {
+                object.portal = None;
+                object.collision_type = CollisionType::Bounce;
+                object.color = Color { r: 0.025, g: 0.025, b: 0.025 };
+                self.last_portal = None;
+                self.static_version += 1;
+            }
+        }
+    }
+
+    // KeyO links the object under the mouse both ways with the selected portal, again makes it exit only
+    // and a third time unlinks it. Linking gives both a new name, which leaves whatever they were linked
+    // to before on their own, and keeps the way they face.
+    pub(super) fn link_portal(&mut self, id:usize){
+        let Some(selected) = self.last_portal.filter(|selected| *selected != id) else {
+            return;
+        };
+        let Some(name) = self.objects[selected].portal.as_ref().map(|portal| portal.name.clone()) else {
+            return;
+        };
+        let linked = self.objects[id].portal.as_ref().is_some_and(|portal| portal.name == name);
+        if !linked {
+            let name = self.unused_portal_name();
+            if let Some(portal) = &mut self.objects[selected].portal {
+                portal.name = name.clone();
+            }
+            match &mut self.objects[id].portal {
+                Some(portal) => portal.name = name,
+                None => self.objects[id].portal = Some(Portal::new(name)),
+            }
+            self.objects[id].collision_type = CollisionType::None;
+        }
+        else {
+            let unused = self.unused_portal_name();
+            if let Some(portal) = &mut self.objects[id].portal {
+                if portal.exit_only {
+                    portal.name = unused;
+                    portal.exit_only = false;
+                }
+                else {
+                    portal.exit_only = true;
+                }
+            }
+        }
+        self.set_portal_color(id);
+    }
+
+    // Arrows for the way portals face and lines between linked ones, the selected portal is highlighted.
+    pub(super) fn draw_portals(&self, geometry:&mut Geometry){
+        for (id, object) in self.objects.iter().enumerate(){
+            let Some(portal) = &object.portal else {
+                continue;
+            };
+            let center = object.rect.center();
+            let tip = center.add(portal.facing.mul(object.rect.extent(portal.facing) + 20.0));
+            let (r, g, b) = if self.last_portal == Some(id) { (1.0, 1.0, 1.0) } else { (object.color.r, object.color.g, object.color.b) };
+            geometry.add_line(center.x, center.y, tip.x, tip.y, 3.0, r, g, b);
+            geometry.add_sdf_quad(tip.x - 5.0, tip.y - 5.0, 10.0, 10.0, [r, g, b, 1.0], 5.0, 0.0, 0.0);
+            // Each line is drawn once, from the entrance when only the other end is exit only since several
+            // entrances can share that exit.
+            let drawn_here = |other_id:usize| match &self.objects[other_id].portal {
+                Some(other) if other.exit_only != portal.exit_only => other.exit_only,
+                _ => other_id > id,
+            };
+            match self.linked_portal(id) {
+                Some(other_id) if drawn_here(other_id) => {
+                    let other = self.objects[other_id].rect.center();
+                    geometry.add_line(center.x, center.y, other.x, other.y, 1.0, 0.6, 0.6, 0.6);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A portal in the floor facing up linked to one in a wall facing right, and a box falling into the first.
+    fn level() -> VectorGraphics{
+        let portal = |rect:Rect, facing:Vector2| Object {
+            portal: Some(Portal { facing, ..Portal::new("a".to_string()) }),
+            collision_type: CollisionType::None,
+            ..Object::new(rect, Color { r: 0.2, g: 1.0, b: 0.2 })
+        };
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.add_object(portal(Rect { x: 0.0, y: 300.0, width: 100.0, height: 20.0 }, PORTAL_FACINGS[0]));
+        vector_graphics.add_object(portal(Rect { x: 500.0, y: 100.0, width: 20.0, height: 100.0 }, PORTAL_FACINGS[1]));
+        vector_graphics.add_object(Object {
+            velocity: Vector2 { x: 1.0, y: 8.0 },
+            ..Object::new(Rect { x: 30.0, y: 270.0, width: 40.0, height: 40.0 }, Color { r: 1.0, g: 1.0, b: 1.0 })
+        });
+        vector_graphics
+    }
+
+    #[test]
+    fn velocity_turns_from_the_entrance_to_the_exit(){
+        let mut vector_graphics = level();
+        assert!(vector_graphics.enter_portal(2, 0));
+        let object = &vector_graphics.objects[2];
+        // Falling in and drifting right comes out going right and drifting up.
+        assert!((object.velocity.x - 8.0).abs() < 0.001 && (object.velocity.y + 1.0).abs() < 0.001);
+        assert!((object.rect.x - (520.0 + CONTACT_SKIN)).abs() < 0.001);
+        assert!((object.rect.center().y - 150.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn portals_wait_out_the_cooldown_and_work_both_ways(){
+        let mut vector_graphics = level();
+        assert!(vector_graphics.enter_portal(2, 0));
+        // Straight back in through the exit does nothing until the cooldown is over.
+        assert!(!vector_graphics.enter_portal(2, 1));
+        vector_graphics.frame += PORTAL_COOLDOWN_FRAMES;
+        vector_graphics.objects[2].velocity = Vector2 { x: -8.0, y: 0.0 };
+        assert!(vector_graphics.enter_portal(2, 1));
+        let object = &vector_graphics.objects[2];
+        assert!(object.velocity.x.abs() < 0.001 && (object.velocity.y + 8.0).abs() < 0.001);
+        assert!((object.rect.y + object.rect.height - (300.0 - CONTACT_SKIN)).abs() < 0.001);
+    }
+
+    #[test]
+    fn exit_only_and_blocked_portals_cannot_be_entered(){
+        let mut vector_graphics = level();
+        if let Some(portal) = &mut vector_graphics.objects[0].portal {
+            portal.exit_only = true;
+        }
+        assert!(!vector_graphics.enter_portal(2, 0));
+        if let Some(portal) = &mut vector_graphics.objects[0].portal {
+            portal.exit_only = false;
+        }
+        vector_graphics.add_object(Object::new(Rect { x: 520.0, y: 100.0, width: 100.0, height: 100.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }));
+        assert!(!vector_graphics.enter_portal(2, 0));
+        assert_eq!(vector_graphics.objects[2].rect.x, 30.0);
+    }
+
+    #[test]
+    fn old_entrances_to_the_same_target_share_its_exit(){
+        let entrance = |x:f32| Object {
+            collision_type: CollisionType::PortalTo,
+            target: 2,
+            ..Object::new(Rect { x, y: 300.0, width: 100.0, height: 20.0 }, Color { r: 0.2, g: 1.0, b: 0.2 })
+        };
+        let mut objects = vec![entrance(0.0), entrance(200.0), Object::new(Rect { x: 500.0, y: 0.0, width: 100.0, height: 20.0 }, Color { r: 0.2, g: 0.2, b: 1.0 })];
+        upgrade_portals(&mut objects);
+        let mut vector_graphics = VectorGraphics::new();
+        for object in objects {
+            vector_graphics.add_object(object);
+        }
+        assert!(vector_graphics.objects[2].portal.as_ref().is_some_and(|portal| portal.exit_only));
+        assert_eq!(vector_graphics.linked_portal(0), Some(2));
+        assert_eq!(vector_graphics.linked_portal(1), Some(2));
+        vector_graphics.add_object(Object::new(Rect { x: 0.0, y: 0.0, width: 40.0, height: 40.0 }, Color { r: 1.0, g: 1.0, b: 1.0 }));
+        for (entrance, x) in [(0, 30.0), (1, 230.0)] {
+            vector_graphics.frame += PORTAL_COOLDOWN_FRAMES;
+            vector_graphics.objects[3].rect.x = x;
+            vector_graphics.objects[3].rect.y = 270.0;
+            vector_graphics.objects[3].velocity = Vector2 { x: 0.0, y: 8.0 };
+            vector_graphics.moved(3);
+            assert!(vector_graphics.enter_portal(3, entrance));
+            // Both come out under the exit, still falling.
+            let object = &vector_graphics.objects[3];
+            assert!((object.rect.y - (20.0 + CONTACT_SKIN)).abs() < 0.001 && (object.velocity.y - 8.0).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn linking_keeps_the_facing_and_leaves_the_old_link_alone(){
+        let mut vector_graphics = level();
+        let other = vector_graphics.objects.len();
+        vector_graphics.add_object(Object {
+            portal: Some(Portal { facing: PORTAL_FACINGS[3], exit_only: true, ..Portal::new("b".to_string()) }),
+            collision_type: CollisionType::None,
+            ..Object::new(Rect { x: 300.0, y: 0.0, width: 20.0, height: 100.0 }, Color { r: 0.2, g: 0.2, b: 1.0 })
+        });
+        vector_graphics.last_portal = Some(0);
+        vector_graphics.link_portal(other);
+        assert_eq!(vector_graphics.linked_portal(0), Some(other));
+        assert_eq!(vector_graphics.linked_portal(1), None);
+        assert!(vector_graphics.objects[0].portal.as_ref().is_some_and(|portal| portal.facing == PORTAL_FACINGS[0]));
+        assert!(vector_graphics.objects[other].portal.as_ref().is_some_and(|portal| portal.facing == PORTAL_FACINGS[3] && portal.exit_only));
+    }
+}
@@ -0,0 +1,299 @@
+use super::*;
+
+// Which objects a query looks at. Starts out accepting everything and is narrowed down with the builder methods.
+#[derive(Clone, Copy)]
+pub(super) struct QueryFilter{
+    // One bit per collision type, see collision_bit.
+    collision_types:u32,
+    // Only objects of this faction, any faction without one.
+    faction:Option<u32>,
+    damageable:bool,
+    ignore:Option<usize>,
+}
+
+fn collision_bit(collision_type:&CollisionType) -> u32{
+    1 << match collision_type {
+        CollisionType::Bounce => 0,
+        CollisionType::PortalTo => 1,
+        CollisionType::None => 2,
+        CollisionType::OneWay => 3,
+        CollisionType::Ladder => 4,
+    }
+}
+
+impl QueryFilter{
+    pub(super) fn all() -> Self{
+        QueryFilter { collision_types: u32::MAX, faction: None, damageable: false, ignore: None }
+    }
+
+    // Only what blocks movement, walls, floors and one way platforms.
+    // Like of_faction and damageable, only game logic on top of the engine narrows queries down this way.
+    #[allow(dead_code)]
+    pub(super) fn solid() -> Self{
+        Self::all().of_collision_types(&[CollisionType::Bounce, CollisionType::OneWay])
+    }
+
+    pub(super) fn of_collision_types(self, collision_types:&[CollisionType]) -> Self{
+        QueryFilter { collision_types: collision_types.iter().fold(0, |bits, collision_type| bits | collision_bit(collision_type)), ..self }
+    }
+
+    #[allow(dead_code)]
+    pub(super) fn of_faction(self, faction:u32) -> Self{
+        QueryFilter { faction: Some(faction), ..self }
+    }
+
+    // Only objects with health.
+    #[allow(dead_code)]
+    pub(super) fn damageable(self) -> Self{
+        QueryFilter { damageable: true, ..self }
+    }
+
+    // Leaves out one object, usually the one asking.
+    pub(super) fn ignoring(self, id:usize) -> Self{
+        QueryFilter { ignore: Some(id), ..self }
+    }
+
+    fn accepts(&self, id:usize, object:&Object) -> bool{
+        self.ignore != Some(id)
+            && self.collision_types & collision_bit(&object.collision_type) != 0
+            && self.faction.map_or(true, |faction| faction == object.faction)
+            && (!self.damageable || object.max_health > 0)
+    }
+}
+
+// The first object a ray runs into, how far along the ray it is and the surface normal there.
+// Only the casts themselves read the distance, the rest is for their callers.
+pub(super) struct RayHit{
+    #[allow(dead_code)]
+    pub id:usize,
+    pub distance:f32,
+    #[allow(dead_code)]
+    pub normal:Vector2,
+}
+
+impl VectorGraphics {
+    // Nothing is further from point than the far corner of the grid, so queries don't need to reach further.
+    // Keeps areas built from huge or infinite distances finite and as precise as the level they cover.
+    fn reach(&self, point:Vector2, distance:f32) -> f32{
+        let Some(bounds) = self.grid.bounds() else {
+            return 0.0;
+        };
+        let far_x = (bounds.x - point.x).abs().max((bounds.x + bounds.width - point.x).abs());
+        let far_y = (bounds.y - point.y).abs().max((bounds.y + bounds.height - point.y).abs());
+        distance.min(Vector2 { x: far_x, y: far_y }.magnitude())
+    }
+
+    // Objects are hit by their collision shape, so slopes and rotated bodies are hit where they are drawn.
+    // Ties go to the lower id so the result doesn't depend on the grid.
+    // The engine itself never casts rays, this and the other casts are for game logic like enemy sight.
+    #[allow(dead_code)]
+    pub(super) fn raycast(&self, origin:Vector2, direction:Vector2, max_distance:f32, filter:QueryFilter) -> Option<RayHit>{
+        let direction = direction.normalize();
+        if direction.magnitude() == 0.0 {
+            return None;
+        }
+        let end = origin.add(direction.mul(self.reach(origin, max_distance)));
+        let area = Rect { x: origin.x.min(end.x), y: origin.y.min(end.y), width: (end.x - origin.x).abs(), height: (end.y - origin.y).abs() };
+        let mut first_hit:Option<RayHit> = None;
+        for id in self.grid.query(&area){
+            if !filter.accepts(id, &self.objects[id]) {
+                continue;
+            }
+            match polygon::raycast(&self.objects[id].polygon(), origin, direction, max_distance) {
+                Some((distance, normal)) if first_hit.as_ref().map_or(true, |first| distance < first.distance) => {
+                    first_hit = Some(RayHit { id, distance, normal });
+                }
+                _ => {}
+            }
+        }
+        first_hit
+    }
+
+    #[allow(dead_code)]
+    pub(super) fn segment_cast(&self, start:Vector2, end:Vector2, filter:QueryFilter) -> Option<RayHit>{
+        let offset = end.sub(start);
+        self.raycast(start, offset, offset.magnitude(), filter)
+    }
+
+    // Everything whose rect overlaps area, in id order.
+    pub(super) fn overlap_rect(&self, area:&Rect, filter:QueryFilter) -> Vec<usize>{
+        self.grid.query(area).into_iter()
+            .filter(|id| filter.accepts(*id, &self.objects[*id]) && Rect::overlaps(area, &self.objects[*id].rect))
+            .collect()
+    }
+
+    // Everything whose rect contains point, in id order.
+    pub(super) fn objects_at_point(&self, point:Vector2, filter:QueryFilter) -> Vec<usize>{
+        let area = Rect { x: point.x, y: point.y, width: 0.0, height: 0.0 };
+        self.grid.query(&area).into_iter()
+            .filter(|id| filter.accepts(*id, &self.objects[*id]) && self.objects[*id].rect.contains(point))
+            .collect()
+    }
+
+    // The object of the faction whose center is closest to point, within max_distance. For targeting in
+    // game logic, nothing in the engine needs it.
+    #[allow(dead_code)]
+    pub(super) fn nearest_of_faction(&self, point:Vector2, faction:u32, max_distance:f32, filter:QueryFilter) -> Option<usize>{
+        let filter = filter.of_faction(faction);
+        let reach = self.reach(point, max_distance);
+        let area = Rect { x: point.x - reach, y: point.y - reach, width: reach*2.0, height: reach*2.0 };
+        let mut nearest:Option<(usize, f32)> = None;
+        for id in self.grid.query(&area){
+            if !filter.accepts(id, &self.objects[id]) {
+                continue;
+            }
+            let distance = self.objects[id].rect.center().sub(point).magnitude();
+            if distance <= max_distance && nearest.map_or(true, |(_, nearest_distance)| distance < nearest_distance) {
+                nearest = Some((id, distance));
+            }
+        }
+        nearest.map(|(id, _)| id)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x:f32, y:f32, width:f32, height:f32) -> Rect{
+        Rect { x, y, width, height }
+    }
+
+    fn level(objects:Vec<Object>) -> VectorGraphics{
+        let mut vector_graphics = VectorGraphics::new();
+        for object in objects{
+            vector_graphics.add_object(object);
+        }
+        vector_graphics
+    }
+
+    // Along y 0 to 100, left to right: a wall, a ladder over the end of it, a harmless enemy spike and an enemy.
+    fn row() -> VectorGraphics{
+        let gray = Color { r: 0.5, g: 0.5, b: 0.5 };
+        level(vec![
+            Object::new(rect(200.0, 0.0, 50.0, 100.0), gray),
+            Object { collision_type: CollisionType::Ladder, ..Object::new(rect(230.0, 0.0, 40.0, 100.0), gray) },
+            Object { faction: FACTION_ENEMY, ..Object::new(rect(300.0, 40.0, 20.0, 20.0), gray) },
+            Object { faction: FACTION_ENEMY, health: 20, max_health: 20, ..Object::new(rect(400.0, 0.0, 50.0, 100.0), gray) },
+        ])
+    }
+
+    #[test]
+    fn of_collision_types_keeps_only_the_given_types(){
+        let gray = Color { r: 0.5, g: 0.5, b: 0.5 };
+        let object = |collision_type:CollisionType| Object { collision_type, ..Object::new(rect(0.0, 0.0, 100.0, 100.0), gray) };
+        let vector_graphics = level(vec![
+            object(CollisionType::Bounce), object(CollisionType::PortalTo), object(CollisionType::None), object(CollisionType::OneWay), object(CollisionType::Ladder),
+        ]);
+        let area = rect(25.0, 25.0, 50.0, 50.0);
+        let found = |filter:QueryFilter| vector_graphics.overlap_rect(&area, filter);
+        assert_eq!(found(QueryFilter::all()), vec![0, 1, 2, 3, 4]);
+        assert_eq!(found(QueryFilter::all().of_collision_types(&[CollisionType::Ladder])), vec![4]);
+        assert_eq!(found(QueryFilter::all().of_collision_types(&[CollisionType::PortalTo, CollisionType::None])), vec![1, 2]);
+        assert_eq!(found(QueryFilter::solid()), vec![0, 3]);
+        assert!(found(QueryFilter::all().of_collision_types(&[])).is_empty());
+    }
+
+    #[test]
+    fn raycast_returns_the_first_hit_with_its_distance_and_normal(){
+        let vector_graphics = row();
+        let hit = vector_graphics.raycast(Vector2 { x: 0.0, y: 50.0 }, Vector2 { x: 2.0, y: 0.0 }, 1000.0, QueryFilter::all()).unwrap();
+        assert_eq!(hit.id, 0);
+        assert!((hit.distance - 200.0).abs() < 1.0e-3);
+        assert!(hit.normal == Vector2 { x: -1.0, y: 0.0 });
+
+        let hit = vector_graphics.raycast(Vector2 { x: 500.0, y: 50.0 }, Vector2 { x: -1.0, y: 0.0 }, 1000.0, QueryFilter::all()).unwrap();
+        assert_eq!(hit.id, 3);
+        assert!((hit.distance - 50.0).abs() < 1.0e-3);
+        assert!(hit.normal == Vector2 { x: 1.0, y: 0.0 });
+
+        // Downwards onto the top of the wall.
+        let hit = vector_graphics.raycast(Vector2 { x: 210.0, y: -30.0 }, Vector2 { x: 0.0, y: 1.0 }, 1000.0, QueryFilter::all()).unwrap();
+        assert_eq!(hit.id, 0);
+        assert!((hit.distance - 30.0).abs() < 1.0e-3);
+        assert!(hit.normal == Vector2 { x: 0.0, y: -1.0 });
+    }
+
+    #[test]
+    fn raycast_stops_at_max_distance(){
+        let vector_graphics = row();
+        let origin = Vector2 { x: 0.0, y: 50.0 };
+        let right = Vector2 { x: 1.0, y: 0.0 };
+        assert!(vector_graphics.raycast(origin, right, 199.0, QueryFilter::all()).is_none());
+        assert!(vector_graphics.raycast(origin, Vector2 { x: 0.0, y: 0.0 }, 1000.0, QueryFilter::all()).is_none());
+    }
+
+    #[test]
+    fn infinite_distances_only_look_where_there_are_objects(){
+        let vector_graphics = row();
+        let diagonal = Vector2 { x: 1.0, y: 1.0 };
+        let hit = vector_graphics.raycast(Vector2 { x: 170.0, y: 20.0 }, diagonal, f32::INFINITY, QueryFilter::all()).unwrap();
+        assert_eq!(hit.id, 0);
+        assert!((hit.distance - 30.0 * std::f32::consts::SQRT_2).abs() < 1.0e-3);
+        let hit = vector_graphics.raycast(Vector2 { x: 500.0, y: 50.0 }, Vector2 { x: -1.0, y: 0.0 }, f32::INFINITY, QueryFilter::all()).unwrap();
+        assert_eq!(hit.id, 3);
+        assert!(vector_graphics.raycast(Vector2 { x: 0.0, y: 50.0 }, Vector2 { x: -1.0, y: -1.0 }, f32::INFINITY, QueryFilter::all()).is_none());
+        assert_eq!(vector_graphics.nearest_of_faction(Vector2 { x: -1.0e6, y: 50.0 }, FACTION_ENEMY, f32::INFINITY, QueryFilter::all()), Some(2));
+    }
+
+    #[test]
+    fn segment_cast_only_hits_between_its_ends(){
+        let vector_graphics = row();
+        let start = Vector2 { x: 0.0, y: 50.0 };
+        assert!(vector_graphics.segment_cast(start, Vector2 { x: 150.0, y: 50.0 }, QueryFilter::all()).is_none());
+        let hit = vector_graphics.segment_cast(start, Vector2 { x: 300.0, y: 50.0 }, QueryFilter::all()).unwrap();
+        assert_eq!(hit.id, 0);
+        assert!((hit.distance - 200.0).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn filters_skip_other_factions_and_non_solids(){
+        let vector_graphics = row();
+        let origin = Vector2 { x: 0.0, y: 50.0 };
+        let right = Vector2 { x: 1.0, y: 0.0 };
+        let enemies = QueryFilter::all().of_faction(FACTION_ENEMY);
+        assert_eq!(vector_graphics.raycast(origin, right, 1000.0, enemies).unwrap().id, 2);
+        assert_eq!(vector_graphics.raycast(origin, right, 1000.0, enemies.damageable()).unwrap().id, 3);
+        assert!(vector_graphics.raycast(origin, right, 1000.0, QueryFilter::all().of_faction(FACTION_PLAYER)).is_none());
+        // The ladder is skipped by solid queries, starting inside the wall doesn't hit it.
+        let inside_wall = Vector2 { x: 240.0, y: 50.0 };
+        assert_eq!(vector_graphics.raycast(inside_wall, right, 1000.0, QueryFilter::solid()).unwrap().id, 2);
+        assert_eq!(vector_graphics.raycast(inside_wall, right, 1000.0, QueryFilter::solid().ignoring(2)).unwrap().id, 3);
+    }
+
+    #[test]
+    fn any_faction_includes_factions_past_31(){
+        let gray = Color { r: 0.5, g: 0.5, b: 0.5 };
+        let vector_graphics = level(vec![
+            Object { faction: 32, ..Object::new(rect(0.0, 0.0, 100.0, 100.0), gray) },
+            Object { faction: u32::MAX, ..Object::new(rect(200.0, 0.0, 100.0, 100.0), gray) },
+        ]);
+        let point = Vector2 { x: 50.0, y: 50.0 };
+        assert_eq!(vector_graphics.objects_at_point(point, QueryFilter::all()), vec![0]);
+        assert_eq!(vector_graphics.objects_at_point(point, QueryFilter::all().of_faction(32)), vec![0]);
+        assert!(vector_graphics.objects_at_point(point, QueryFilter::all().of_faction(0)).is_empty());
+        assert_eq!(vector_graphics.nearest_of_faction(point, u32::MAX, 1000.0, QueryFilter::all()), Some(1));
+        assert_eq!(vector_graphics.find_object_at_point(Vector2 { x: 250.0, y: 50.0 }), Some(1));
+    }
+
+    #[test]
+    fn objects_at_point_returns_everything_under_it(){
+        let vector_graphics = row();
+        let point = Vector2 { x: 240.0, y: 50.0 };
+        assert_eq!(vector_graphics.objects_at_point(point, QueryFilter::all()), vec![0, 1]);
+        assert_eq!(vector_graphics.objects_at_point(point, QueryFilter::solid()), vec![0]);
+        assert_eq!(vector_graphics.objects_at_point(point, QueryFilter::all().ignoring(0)), vec![1]);
+        assert!(vector_graphics.objects_at_point(Vector2 { x: 100.0, y: 50.0 }, QueryFilter::all()).is_empty());
+    }
+
+    #[test]
+    fn nearest_of_faction_is_the_closest_center_in_range(){
+        let vector_graphics = row();
+        let point = Vector2 { x: 0.0, y: 50.0 };
+        assert_eq!(vector_graphics.nearest_of_faction(point, FACTION_ENEMY, 1000.0, QueryFilter::all()), Some(2));
+        assert_eq!(vector_graphics.nearest_of_faction(point, FACTION_ENEMY, 1000.0, QueryFilter::all().damageable()), Some(3));
+        assert_eq!(vector_graphics.nearest_of_faction(point, FACTION_ENEMY, 300.0, QueryFilter::all()), None);
+        assert_eq!(vector_graphics.nearest_of_faction(point, 0, 1000.0, QueryFilter::all()), Some(0));
+    }
+}
@@ -0,0 +1,276 @@
+use super::*;
+
+// Optional rigid body for props like crates and seesaws. size is the box before rotation, the object's
+// rect is kept as the bounding box of the rotated box like it is for polygons. Pinned bodies only rotate
+// around their center. Characters keep moving kinematically and count as infinitely heavy.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub(super) struct RigidBody{
+    pub mass:f32,
+    pub size:Vector2,
+    pub angle:f32,
+    pub angular_velocity:f32,
+    pub pinned:bool,
+}
+
+const ANGULAR_DAMPING:f32 = 0.99;
+// How heavy characters standing on a body are, so they can tip a seesaw.
+const CHARACTER_MASS:f32 = 1.0;
+
+fn cross(a:Vector2, b:Vector2) -> f32{
+    a.x * b.y - a.y * b.x
+}
+
+// The velocity of a point at offset from the center of something rotating at angular_velocity.
+fn spin(angular_velocity:f32, offset:Vector2) -> Vector2{
+    Vector2 { x: -angular_velocity * offset.y, y: angular_velocity * offset.x }
+}
+
+impl RigidBody{
+    pub fn new(size:Vector2, mass:f32, pinned:bool) -> Self{
+        RigidBody { mass, size, angle: 0.0, angular_velocity: 0.0, pinned }
+    }
+
+    pub fn inertia(&self) -> f32{
+        self.mass * (self.size.x * self.size.x + self.size.y * self.size.y) / 12.0
+    }
+
+    fn axes(&self) -> (Vector2, Vector2){
+        let (sin, cos) = sin_cos(self.angle);
+        (Vector2 { x: cos * self.size.x / 2.0, y: sin * self.size.x / 2.0 }, Vector2 { x: -sin * self.size.y / 2.0, y: cos * self.size.y / 2.0 })
+    }
+
+    pub fn corners(&self, center:Vector2) -> Vec<Vector2>{
+        let (x, y) = self.axes();
+        vec![
+            center.sub(x).sub(y),
+            center.add(x).sub(y),
+            center.add(x).add(y),
+            center.sub(x).add(y),
+        ]
+    }
+
+    pub fn bounds(&self, center:Vector2) -> Rect{
+        let (x, y) = self.axes();
+        let half = Vector2 { x: x.x.abs() + y.x.abs(), y: x.y.abs() + y.y.abs() };
+        Rect { x: center.x - half.x, y: center.y - half.y, width: half.x * 2.0, height: half.y * 2.0 }
+    }
+}
+
+impl VectorGraphics {
+    fn inverse_mass(&self, id:usize) -> (f32, f32){
+        match &self.objects[id].body {
+            Some(body) => (if body.pinned { 0.0 } else { 1.0 / body.mass }, 1.0 / body.inertia()),
+            None => (0.0, 0.0),
+        }
+    }
+
+    pub(super) fn angular_velocity(&self, id:usize) -> f32{
+        self.objects[id].body.map(|body| body.angular_velocity).unwrap_or(0.0)
+    }
+
+    fn relative_velocity(&self, id:usize, other_id:usize, offset:Vector2, other_offset:Vector2) -> Vector2{
+        let velocity = self.objects[id].velocity.add(spin(self.angular_velocity(id), offset));
+        let other_velocity = self.objects[other_id].velocity.add(spin(self.angular_velocity(other_id), other_offset));
+        velocity.sub(other_velocity)
+    }
+
+    pub(super) fn place_body(&mut self, id:usize, center:Vector2){
+        let object = &mut self.objects[id];
+        if let Some(body) = &object.body {
+            object.rect = body.bounds(center);
+        }
+        self.moved(id);
+    }
+
+    pub(super) fn apply_impulse(&mut self, id:usize, impulse:Vector2, offset:Vector2){
+        let (inverse_mass, inverse_inertia) = self.inverse_mass(id);
+        let object = &mut self.objects[id];
+        if let Some(body) = &mut object.body {
+            object.velocity = object.velocity.add(impulse.mul(inverse_mass));
+            body.angular_velocity += cross(offset, impulse) * inverse_inertia;
+        }
+    }
+
+    // Characters resting on the body press down on it with their weight.
+    fn apply_weights(&mut self, id:usize){
+        for other_id in self.grid.query(&self.objects[id].rect.expand(CONTACT_SKIN*2.0)){
+            let other = &self.objects[other_id];
+            if other_id == id || other.body.is_some() || other.gravity == 0.0 || !other.grounded || self.ground(other_id) != Some(id) {
+                continue;
+            }
+            let down = other.down();
+            let feet = other.rect.center().add(down.scale(other.rect.width/2.0, other.rect.height/2.0));
+            let weight = down.mul(other.gravity * CHARACTER_MASS);
+            let offset = feet.sub(self.objects[id].rect.center());
+            self.apply_impulse(id, weight, offset);
+        }
+    }
+
+    // Separates the body from other and applies the contact impulses, with friction, to both. Objects
+    // without a body only push, with their own velocity.
+    fn resolve_body_contact(&mut self, id:usize, other_id:usize){
+        if other_id == id || !matches!(self.objects[other_id].collision_type, CollisionType::Bounce) {
+            return;
+        }
+        let Some(manifold) = polygon::collide(&self.objects[id].polygon(), &self.objects[other_id].polygon()) else {
+            return;
+        };
+        let normal = manifold.normal;
+        let (inverse_mass, inverse_inertia) = self.inverse_mass(id);
+        let (other_inverse_mass, other_inverse_inertia) = self.inverse_mass(other_id);
+        let total = inverse_mass + other_inverse_mass;
+        if total > 0.0 {
+            let center = self.objects[id].rect.center().add(normal.mul(manifold.depth * inverse_mass / total));
+            self.place_body(id, center);
+            if other_inverse_mass > 0.0 {
+                let other_center = self.objects[other_id].rect.center().sub(normal.mul(manifold.depth * other_inverse_mass / total));
+                self.place_body(other_id, other_center);
+            }
+        }
+        else if inverse_inertia > 0.0 {
+            // A pinned body can only be separated by turning it. Clipping can leave no contact points when
+            // the polygons overlap awkwardly, then there is nothing to turn it about.
+            if let Some(contact) = manifold.contacts.first() {
+                let offset = contact.sub(self.objects[id].rect.center());
+                let center = self.objects[id].rect.center();
+                if let Some(body) = &mut self.objects[id].body {
+                    body.angle += manifold.depth * cross(offset, normal) / offset.dot(offset).max(1.0);
+                }
+                self.place_body(id, center);
+            }
+        }
+        let restitution = self.restitution(id, other_id);
+        let friction = (self.objects[id].material.friction * self.objects[other_id].material.friction).min(1.0);
+        let count = manifold.contacts.len() as f32;
+        let offsets:Vec<(Vector2, Vector2)> = manifold.contacts.iter()
+            .map(|contact| (contact.sub(self.objects[id].rect.center()), contact.sub(self.objects[other_id].rect.center())))
+            .collect();
+        let effective_mass = |offset:Vector2, other_offset:Vector2, direction:Vector2| {
+            let arm = cross(offset, direction);
+            let other_arm = cross(other_offset, direction);
+            inverse_mass + other_inverse_mass + arm * arm * inverse_inertia + other_arm * other_arm * other_inverse_inertia
+        };
+        // The impulses of all contacts are worked out from the same velocities before any are applied,
+        // so a box resting on two corners doesn't start spinning.
+        let impulses:Vec<f32> = offsets.iter().map(|(offset, other_offset)| {
+            let into = self.relative_velocity(id, other_id, *offset, *other_offset).dot(normal);
+            let mass = effective_mass(*offset, *other_offset, normal);
+            if into >= 0.0 || mass == 0.0 {
+                return 0.0;
+            }
+            let bounce = if -into < MIN_BOUNCE_SPEED { 0.0 } else { restitution };
+            -(1.0 + bounce) * into / mass / count
+        }).collect();
+        for ((offset, other_offset), impulse) in offsets.iter().zip(&impulses) {
+            self.apply_impulse(id, normal.mul(*impulse), *offset);
+            self.apply_impulse(other_id, normal.mul(-impulse), *other_offset);
+        }
+        let friction_impulses:Vec<Vector2> = offsets.iter().zip(&impulses).map(|((offset, other_offset), impulse)| {
+            let velocity = self.relative_velocity(id, other_id, *offset, *other_offset);
+            let sliding = velocity.sub(normal.mul(velocity.dot(normal)));
+            if *impulse == 0.0 || sliding.magnitude() < 1.0e-4 {
+                return Vector2 { x: 0.0, y: 0.0 };
+            }
+            let tangent = sliding.normalize();
+            let limit = friction * impulse;
+            tangent.mul((-velocity.dot(tangent) / effective_mass(*offset, *other_offset, tangent) / count).clamp(-limit, limit))
+        }).collect();
+        for ((offset, other_offset), impulse) in offsets.iter().zip(friction_impulses) {
+            self.apply_impulse(id, impulse, *offset);
+            self.apply_impulse(other_id, impulse.mul(-1.0), *other_offset);
+        }
+    }
+
+    // Rigid bodies are integrated here instead of going through slide.
+    pub(super) fn step_body(&mut self, id:usize){
+        let Some(mut body) = self.objects[id].body else {
+            return;
+        };
+        body.angle += body.angular_velocity;
+        body.angular_velocity *= ANGULAR_DAMPING;
+        self.objects[id].body = Some(body);
+        let mut carry = Vector2 { x: 0.0, y: 0.0 };
+        if body.pinned {
+            self.objects[id].velocity = Vector2 { x: 0.0, y: 0.0 };
+        }
+        else {
+            self.apply_gravity(id);
+            self.apply_liquid(id);
+            carry = self.apply_forces(id);
+            self.clamp_fall(id);
+        }
+        let center = self.objects[id].rect.center().add(self.objects[id].velocity).add(carry);
+        self.place_body(id, center);
+        self.apply_weights(id);
+        for other_id in self.grid.query(&self.objects[id].rect){
+            self.resolve_body_contact(id, other_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ground() -> Object{
+        Object::new(Rect { x: 0.0, y: 400.0, width: 400.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 })
+    }
+
+    fn body(rect:Rect, pinned:bool) -> Object{
+        Object {
+            gravity: 0.3,
+            body: Some(RigidBody::new(Vector2 { x: rect.width, y: rect.height }, 1.0, pinned)),
+            ..Object::new(rect, Color { r: 0.6, g: 0.4, b: 0.2 })
+        }
+    }
+
+    fn run(objects:Vec<Object>, frames:usize) -> VectorGraphics{
+        let mut vector_graphics = VectorGraphics::new();
+        for object in objects{
+            vector_graphics.add_object(object);
+        }
+        vector_graphics.mode = Mode::Play;
+        for _ in 0..frames{
+            vector_graphics.update();
+        }
+        vector_graphics
+    }
+
+    #[test]
+    fn crates_land_flat_and_stay_put(){
+        let vector_graphics = run(vec![ground(), body(Rect { x: 150.0, y: 200.0, width: 60.0, height: 60.0 }, false)], 200);
+        let falling = &vector_graphics.objects[1];
+        let body = falling.body.unwrap();
+        assert!(body.angle.abs() < 0.01 && body.angular_velocity.abs() < 0.01);
+        assert!((falling.rect.y + falling.rect.height - 400.0).abs() < 1.0);
+        assert!((falling.rect.x - 150.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn crates_landing_on_a_corner_tip_over(){
+        let mut falling = body(Rect { x: 150.0, y: 200.0, width: 60.0, height: 60.0 }, false);
+        if let Some(body) = &mut falling.body {
+            body.angle = 0.3;
+            falling.rect = body.bounds(falling.rect.center());
+        }
+        let vector_graphics = run(vec![ground(), falling], 300);
+        let body = vector_graphics.objects[1].body.unwrap();
+        // Resting on a side, whichever one it is.
+        let quarter = std::f32::consts::FRAC_PI_2;
+        assert!((body.angle - (body.angle / quarter).round() * quarter).abs() < 0.05, "rests at {}", body.angle);
+    }
+
+    #[test]
+    fn seesaws_tip_towards_the_character_on_them(){
+        let seesaw = body(Rect { x: 50.0, y: 300.0, width: 300.0, height: 20.0 }, true);
+        let character = Object {
+            gravity: 0.3,
+            ..Object::new(Rect { x: 290.0, y: 260.0 - CONTACT_SKIN, width: 40.0, height: 40.0 }, Color { r: 1.0, g: 0.5, b: 0.0 })
+        };
+        let vector_graphics = run(vec![seesaw, character], 30);
+        let seesaw = &vector_graphics.objects[0];
+        assert!(seesaw.body.unwrap().angle > 0.05);
+        let center = seesaw.rect.center();
+        assert!((center.x - 200.0).abs() < 0.01 && (center.y - 310.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use super::Rect;
+
+// Uniform grid that buckets object ids by the cells their rects touch. It remembers which cells
+// each id was inserted into so moving objects only touch the grid when they cross a cell border.
+pub struct SpatialGrid{
+    cell_size:f32,
+    cells:HashMap<(i32,i32), Vec<usize>>,
+    ranges:HashMap<usize, (i32,i32,i32,i32)>,
+    // Every cell anything was inserted into since the last clear is inside these. Queries are clamped
+    // to them, so a query for a huge or infinite rect doesn't walk billions of empty cells.
+    bounds:Option<(i32,i32,i32,i32)>,
+}
+
+impl SpatialGrid{
+    pub fn new(cell_size:f32) -> Self{
+        SpatialGrid { cell_size, cells: HashMap::new(), ranges: HashMap::new(), bounds: None }
+    }
+
+    fn cell_range(&self, rect:&Rect) -> (i32, i32, i32, i32){
+        (
+            (rect.x / self.cell_size).floor() as i32,
+            (rect.y / self.cell_size).floor() as i32,
+            ((rect.x + rect.width) / self.cell_size).floor() as i32,
+            ((rect.y + rect.height) / self.cell_size).floor() as i32,
+        )
+    }
+
+    pub fn clear(&mut self){
+        self.cells.clear();
+        self.ranges.clear();
+        self.bounds = None;
+    }
+
+    pub fn insert(&mut self, id:usize, rect:&Rect){
+        let range = self.cell_range(rect);
+        let (min_x, min_y, max_x, max_y) = range;
+        for x in min_x..=max_x{
+            for y in min_y..=max_y{
+                self.cells.entry((x, y)).or_default().push(id);
+            }
+        }
+        self.ranges.insert(id, range);
+        self.bounds = Some(match self.bounds {
+            Some((bounds_min_x, bounds_min_y, bounds_max_x, bounds_max_y)) => {
+                (bounds_min_x.min(min_x), bounds_min_y.min(min_y), bounds_max_x.max(max_x), bounds_max_y.max(max_y))
+            }
+            None => range,
+        });
+    }
+
+    pub fn remove(&mut self, id:usize){
+        if let Some((min_x, min_y, max_x, max_y)) = self.ranges.remove(&id) {
+            for x in min_x..=max_x{
+                for y in min_y..=max_y{
+                    if let Some(ids) = self.cells.get_mut(&(x, y)) {
+                        ids.retain(|other| *other != id);
+                        if ids.is_empty() {
+                            self.cells.remove(&(x, y));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn update(&mut self, id:usize, rect:&Rect){
+        if self.ranges.get(&id) == Some(&self.cell_range(rect)) {
+            return;
+        }
+        self.remove(id);
+        self.insert(id, rect);
+    }
+
+    // The area covered by the cells anything was inserted into since the last clear.
+    pub fn bounds(&self) -> Option<Rect>{
+        let (min_x, min_y, max_x, max_y) = self.bounds?;
+        Some(Rect {
+            x: min_x as f32 * self.cell_size,
+            y: min_y as f32 * self.cell_size,
+            width: (max_x - min_x + 1) as f32 * self.cell_size,
+            height: (max_y - min_y + 1) as f32 * self.cell_size,
+        })
+    }
+
+    // Ids of everything in the cells touched by rect, sorted and without duplicates.
+    // These are candidates only, the caller still has to test the actual rects.
+    pub fn query(&self, rect:&Rect) -> Vec<usize>{
+        let mut result:Vec<usize> = Vec::new();
+        let Some((bounds_min_x, bounds_min_y, bounds_max_x, bounds_max_y)) = self.bounds else {
+            return result;
+        };
+        let (min_x, min_y, max_x, max_y) = self.cell_range(rect);
+        let (min_x, min_y) = (min_x.max(bounds_min_x), min_y.max(bounds_min_y));
+        let (max_x, max_y) = (max_x.min(bounds_max_x), max_y.min(bounds_max_y));
+        for x in min_x..=max_x{
+            for y in min_y..=max_y{
+                if let Some(ids) = self.cells.get(&(x, y)) {
+                    result.extend_from_slice(ids);
+                }
+            }
+        }
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x:f32, y:f32, width:f32, height:f32) -> Rect{
+        Rect { x, y, width, height }
+    }
+
+    #[test]
+    fn inserted_ids_are_found_in_the_cells_they_touch(){
+        let mut grid = SpatialGrid::new(100.0);
+        assert!(grid.query(&rect(0.0, 0.0, 1000.0, 1000.0)).is_empty());
+        grid.insert(1, &rect(10.0, 10.0, 20.0, 20.0));
+        grid.insert(2, &rect(250.0, 10.0, 20.0, 20.0));
+        assert_eq!(grid.query(&rect(50.0, 50.0, 10.0, 10.0)), vec![1]);
+        assert_eq!(grid.query(&rect(210.0, 90.0, 10.0, 10.0)), vec![2]);
+        assert_eq!(grid.query(&rect(0.0, 0.0, 300.0, 100.0)), vec![1, 2]);
+        assert!(grid.query(&rect(0.0, 150.0, 300.0, 10.0)).is_empty());
+        // Negative coordinates get their own cells rather than sharing the ones around zero.
+        grid.insert(3, &rect(-50.0, -50.0, 20.0, 20.0));
+        assert_eq!(grid.query(&rect(-90.0, -90.0, 10.0, 10.0)), vec![3]);
+    }
+
+    #[test]
+    fn updates_move_ids_to_the_cells_they_crossed_into(){
+        let mut grid = SpatialGrid::new(100.0);
+        grid.insert(1, &rect(10.0, 10.0, 20.0, 20.0));
+        grid.update(1, &rect(60.0, 10.0, 20.0, 20.0));
+        assert_eq!(grid.query(&rect(0.0, 0.0, 10.0, 10.0)), vec![1]);
+        grid.update(1, &rect(310.0, 210.0, 20.0, 20.0));
+        assert!(grid.query(&rect(0.0, 0.0, 10.0, 10.0)).is_empty());
+        assert_eq!(grid.query(&rect(390.0, 290.0, 5.0, 5.0)), vec![1]);
+        assert_eq!(grid.ranges[&1], (3, 2, 3, 2));
+    }
+
+    #[test]
+    fn removed_ids_are_gone_from_every_cell(){
+        let mut grid = SpatialGrid::new(100.0);
+        grid.insert(1, &rect(50.0, 50.0, 200.0, 200.0));
+        grid.insert(2, &rect(150.0, 150.0, 10.0, 10.0));
+        grid.remove(1);
+        assert_eq!(grid.query(&rect(0.0, 0.0, 300.0, 300.0)), vec![2]);
+        assert_eq!(grid.cells.len(), 1);
+        // Removing something that isn't there does nothing.
+        grid.remove(1);
+        grid.remove(7);
+        assert_eq!(grid.query(&rect(0.0, 0.0, 300.0, 300.0)), vec![2]);
+    }
+
+    #[test]
+    fn queries_list_objects_spanning_several_cells_once(){
+        let mut grid = SpatialGrid::new(100.0);
+        grid.insert(5, &rect(-150.0, -150.0, 500.0, 300.0));
+        grid.insert(1, &rect(120.0, 20.0, 10.0, 10.0));
+        grid.insert(3, &rect(20.0, 120.0, 10.0, 10.0));
+        assert_eq!(grid.query(&rect(-200.0, -200.0, 600.0, 600.0)), vec![1, 3, 5]);
+        // Huge queries are clamped to the cells that were used.
+        assert_eq!(grid.query(&rect(f32::MIN / 2.0, f32::MIN / 2.0, f32::MAX, f32::MAX)), vec![1, 3, 5]);
+    }
+}
@@ -0,0 +1,299 @@
+use super::*;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum TriggerEventKind{
+    Enter,
+    Stay,
+    Exit,
+}
+
+impl TriggerEventKind{
+    fn next(self) -> Self{
+        match self {
+            TriggerEventKind::Enter => TriggerEventKind::Stay,
+            TriggerEventKind::Stay => TriggerEventKind::Exit,
+            TriggerEventKind::Exit => TriggerEventKind::Enter,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub(super) enum TriggerAction{
+    // Removes the target.
+    OpenDoor,
+    // Spawns an enemy at the center of the target, or of the trigger without one.
+    Spawn,
+    // Queued for the game to show, see take_messages.
+    Message(String),
+    // Hurts the object that set it off, for spikes and pits.
+    Damage(i32),
+    // Ends play and goes back to the editor.
+    EndLevel,
+}
+
+// A sensor zone that runs its action when an object with a controller of the right faction enters,
+// stays in or exits it. Faction 0 is set off by any faction.
+#[derive(Serialize, Deserialize, Clone)]
+pub(super) struct Trigger{
+    pub on:TriggerEventKind,
+    pub action:TriggerAction,
+    pub faction:u32,
+    pub target:Option<usize>,
+    pub once:bool,
+    #[serde(skip)]
+    pub inside:Vec<usize>,
+    #[serde(skip)]
+    pub fired:bool,
+}
+
+// The ids are those of the trigger and of the object that set it off.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TriggerEvent{
+    pub kind:TriggerEventKind,
+    pub trigger:usize,
+    pub other:usize,
+}
+
+impl TriggerEvent{
+    // Keeps the ids pointing at the same objects after the object at id is removed. Returns false
+    // when the event was about that object.
+    pub(super) fn removed(&mut self, id:usize) -> bool{
+        if self.trigger == id || self.other == id {
+            return false;
+        }
+        if self.trigger > id {
+            self.trigger -= 1;
+        }
+        if self.other > id {
+            self.other -= 1;
+        }
+        true
+    }
+}
+
+impl Trigger{
+    pub fn new(action:TriggerAction) -> Self{
+        Trigger { on: TriggerEventKind::Enter, action, faction: FACTION_PLAYER, target: None, once: true, inside: Vec::new(), fired: false }
+    }
+
+    // Keeps the ids in the trigger pointing at the same objects after the object at id is removed.
+    pub fn removed(&mut self, id:usize){
+        self.inside.retain(|other| *other != id);
+        for other in &mut self.inside {
+            if *other > id {
+                *other -= 1;
+            }
+        }
+        self.target = match self.target {
+            Some(target) if target == id => None,
+            Some(target) if target > id => Some(target - 1),
+            target => target,
+        };
+    }
+}
+
+impl VectorGraphics {
+    fn activates(&self, trigger_id:usize, other_id:usize) -> bool{
+        let Some(trigger) = &self.objects[trigger_id].trigger else {
+            return false;
+        };
+        let other = &self.objects[other_id];
+        matches!(other.controller, Controller::Player | Controller::AI) && (trigger.faction == 0 || trigger.faction == other.faction)
+    }
+
+    // Compares what is inside every trigger with the last frame and queues the events, in order of
+    // trigger id and then object id so the same frame always gives the same events.
+    pub(super) fn update_triggers(&mut self){
+        for id in 0..self.objects.len(){
+            if self.objects[id].trigger.is_none() {
+                continue;
+            }
+            let inside:Vec<usize> = self.overlaps(id).into_iter().filter(|other_id| self.activates(id, *other_id)).collect();
+            let Some(trigger) = &mut self.objects[id].trigger else {
+                continue;
+            };
+            let mut events = Vec::new();
+            for other in &inside {
+                let kind = if trigger.inside.contains(other) { TriggerEventKind::Stay } else { TriggerEventKind::Enter };
+                events.push(TriggerEvent { kind, trigger: id, other: *other });
+            }
+            for other in &trigger.inside {
+                if !inside.contains(other) {
+                    events.push(TriggerEvent { kind: TriggerEventKind::Exit, trigger: id, other: *other });
+                }
+            }
+            trigger.inside = inside;
+            self.trigger_events.extend(events);
+        }
+    }
+
+    // Runs the action of every trigger subscribed to a queued event, in the order they were queued,
+    // then hands all of the events on to the game, see take_trigger_events.
+    pub(super) fn process_trigger_events(&mut self){
+        let events = std::mem::take(&mut self.trigger_events);
+        for event in &events {
+            let Some(trigger) = &mut self.objects[event.trigger].trigger else {
+                continue;
+            };
+            if trigger.on != event.kind || (trigger.once && trigger.fired) {
+                continue;
+            }
+            trigger.fired = true;
+            let action = trigger.action.clone();
+            let target = trigger.target;
+            match action {
+                TriggerAction::OpenDoor => {
+                    if let Some(target) = target {
+                        self.objects[target].destroying = true;
+                        self.objects[target].destroy_at_frame = self.frame;
+                    }
+                }
+                TriggerAction::Spawn => {
+                    let center = self.objects[target.unwrap_or(event.trigger)].rect.center();
+                    self.spawn_enemy(center);
+                }
+                TriggerAction::Message(text) => {
+                    self.messages.push(text);
+                }
+                TriggerAction::Damage(damage) => {
+                    self.take_damage(event.other, damage);
+                }
+                TriggerAction::EndLevel => {
+                    self.messages.push("Level complete".to_string());
+                    self.mode = Mode::Edit;
+                }
+            }
+        }
+        self.unread_trigger_events.extend(events);
+    }
+
+    // Every trigger event since the last call, oldest first, whether or not it ran its trigger's action.
+    // Events about objects that have been removed since are left out.
+    pub fn take_trigger_events(&mut self) -> Vec<TriggerEvent>{
+        std::mem::take(&mut self.unread_trigger_events)
+    }
+
+    // Messages from triggers since the last call, oldest first.
+    pub fn take_messages(&mut self) -> Vec<String>{
+        std::mem::take(&mut self.messages)
+    }
+
+    fn spawn_enemy(&mut self, center:Vector2){
+        let rect = Rect { x: center.x - 25.0, y: center.y - 25.0, width: 50.0, height: 50.0 };
+        self.add_object(Object {
+            faction: FACTION_ENEMY,
+            controller: Controller::AI,
+            gravity: 0.3,
+            direction: Vector2 { x: -1.0, y: 0.0 },
+            health: 20,
+            max_health: 20,
+            ..Object::new(rect, Color { r: 1.0, g: 0.0, b: 0.0 })
+        });
+    }
+
+    // KeyX cycles the action of the trigger under the mouse, turning the object into a trigger first.
+    // What sets the trigger off is kept.
+    pub(super) fn cycle_trigger(&mut self, id:usize){
+        let object = &mut self.objects[id];
+        let action = match object.trigger.as_ref().map(|trigger| &trigger.action) {
+            None => Some(TriggerAction::OpenDoor),
+            Some(TriggerAction::OpenDoor) => Some(TriggerAction::Spawn),
+            Some(TriggerAction::Spawn) => Some(TriggerAction::Message(format!("Trigger {}", id))),
+            Some(TriggerAction::Message(_)) => Some(TriggerAction::Damage(10)),
+            Some(TriggerAction::Damage(_)) => Some(TriggerAction::EndLevel),
+            Some(TriggerAction::EndLevel) => None,
+        };
+        object.trigger = action.map(|action| match object.trigger.take() {
+            Some(trigger) => Trigger { action, fired: false, ..trigger },
+            None => Trigger::new(action),
+        });
+        if object.trigger.is_some() {
+            object.collision_type = CollisionType::None;
+            object.color = Color { r: 1.0, g: 0.8, b: 0.0 };
+        }
+        else{
+            object.collision_type = CollisionType::Bounce;
+            object.color = Color { r: 0.025, g: 0.025, b: 0.025 };
+        }
+    }
+
+    // Digit1 cycles the event the trigger under the mouse runs its action on, Digit2 the faction that sets
+    // it off and Digit3 whether it only fires once. There is no text in the editor, so the result goes to
+    // the messages.
+    pub(super) fn configure_trigger(&mut self, id:usize, key:winit::keyboard::KeyCode){
+        let Some(trigger) = &mut self.objects[id].trigger else {
+            return;
+        };
+        match key {
+            winit::keyboard::KeyCode::Digit1 => trigger.on = trigger.on.next(),
+            winit::keyboard::KeyCode::Digit2 => {
+                trigger.faction = match trigger.faction {
+                    0 => FACTION_PLAYER,
+                    FACTION_PLAYER => FACTION_ENEMY,
+                    _ => 0,
+                };
+            }
+            winit::keyboard::KeyCode::Digit3 => trigger.once = !trigger.once,
+            _ => return,
+        }
+        trigger.fired = false;
+        let message = format!("Trigger {} on {:?}, faction {}, once {}", id, trigger.on, trigger.faction, trigger.once);
+        self.messages.push(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A message trigger on exit that anything sets off, with the player dropping through it onto the ground.
+    fn level() -> VectorGraphics{
+        let mut vector_graphics = VectorGraphics::new();
+        vector_graphics.add_object(Object {
+            trigger: Some(Trigger { on: TriggerEventKind::Exit, faction: 0, once: false, ..Trigger::new(TriggerAction::Message("Left".to_string())) }),
+            collision_type: CollisionType::None,
+            ..Object::new(Rect { x: 0.0, y: 0.0, width: 200.0, height: 100.0 }, Color { r: 1.0, g: 0.8, b: 0.0 })
+        });
+        vector_graphics.add_object(Object::new(Rect { x: 0.0, y: 300.0, width: 200.0, height: 50.0 }, Color { r: 0.0, g: 0.0, b: 0.0 }));
+        vector_graphics.add_object(Object {
+            faction: FACTION_PLAYER,
+            controller: Controller::Player,
+            gravity: 0.3,
+            ..Object::new(Rect { x: 80.0, y: -60.0, width: 40.0, height: 40.0 }, Color { r: 1.0, g: 0.5, b: 0.0 })
+        });
+        vector_graphics.mode = Mode::Play;
+        vector_graphics
+    }
+
+    #[test]
+    fn events_reach_the_game_and_run_the_configured_kind(){
+        let mut vector_graphics = level();
+        let mut events = Vec::new();
+        let mut messages = Vec::new();
+        for _ in 0..120{
+            vector_graphics.update();
+            events.extend(vector_graphics.take_trigger_events());
+            messages.extend(vector_graphics.take_messages());
+        }
+        let kinds:Vec<TriggerEventKind> = events.iter().map(|event| event.kind).collect();
+        assert_eq!(kinds.first(), Some(&TriggerEventKind::Enter));
+        assert_eq!(kinds.last(), Some(&TriggerEventKind::Exit));
+        assert!(kinds[1..kinds.len() - 1].iter().all(|kind| *kind == TriggerEventKind::Stay));
+        assert!(kinds.len() > 2);
+        assert!(events.iter().all(|event| event.trigger == 0 && event.other == 2));
+        assert_eq!(messages, vec!["Left".to_string()]);
+        assert!(vector_graphics.take_trigger_events().is_empty());
+    }
+
+    #[test]
+    fn changing_the_action_keeps_the_configuration(){
+        let mut vector_graphics = level();
+        vector_graphics.configure_trigger(0, winit::keyboard::KeyCode::Digit1);
+        vector_graphics.configure_trigger(0, winit::keyboard::KeyCode::Digit2);
+        vector_graphics.configure_trigger(0, winit::keyboard::KeyCode::Digit3);
+        vector_graphics.cycle_trigger(0);
+        let trigger = vector_graphics.objects[0].trigger.as_ref().unwrap();
+        assert!(matches!(trigger.action, TriggerAction::Damage(_)));
+        assert!(trigger.on == TriggerEventKind::Enter && trigger.faction == FACTION_PLAYER && trigger.once);
+    }
+}